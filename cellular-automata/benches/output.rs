@@ -0,0 +1,75 @@
+//! Measures how fast generations can be written out, the way `simple` does it.
+//!
+//! Writes rule 110 to a file in the temp directory, unbuffered, buffered, and
+//! buffered with `--every` and `--only-final` style sampling. Run with
+//! `cargo bench --bench output`, passing a cell count and generation count to
+//! change the size of the run, e.g. `cargo bench --bench output -- 200 1000000`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+use cellular_automata::sample::Sampler;
+use cellular_automata::simple::{Boundary, Packed, State, Wolfram};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/**
+ * Runs the automaton and writes the sampled rows to `out`, giving back how
+ * many bytes that was
+ */
+fn write_run(
+    out: &mut dyn Write,
+    initial: &State,
+    generations: usize,
+    sampler: Sampler,
+) -> io::Result<usize> {
+    let rule = Wolfram::from_code(110);
+    let rows = std::iter::successors(Some(Packed::from_state(initial)), |s| {
+        Some(s.next(&rule, Boundary::Periodic))
+    })
+    .take(generations);
+    let mut bytes = 0;
+    for row in sampler.sample(rows) {
+        let line = format!("|{}|\n", row.to_state());
+        out.write_all(line.as_bytes())?;
+        bytes += line.len();
+    }
+    out.flush()?;
+    Ok(bytes)
+}
+
+fn main() -> io::Result<()> {
+    // `cargo bench` passes `--bench` along, so skip anything that isn't a number
+    let mut args = std::env::args().skip(1).filter_map(|a| a.parse().ok());
+    let cells: usize = args.next().unwrap_or(200);
+    let generations: usize = args.next().unwrap_or(200_000);
+
+    let mut rng = StdRng::seed_from_u64(110);
+    let initial: Vec<u8> = (0..cells).map(|_| rng.gen_range(0..=1)).collect();
+    let initial = State::new(&initial);
+    let path = std::env::temp_dir().join("cellular-automata-output-bench.txt");
+
+    println!("Rule 110, {cells} cells, {generations} generations");
+    let time = |name: &str, out: &mut dyn Write, sampler: Sampler| -> io::Result<()> {
+        let start = Instant::now();
+        let bytes = write_run(out, &initial, generations, sampler)?;
+        let seconds = start.elapsed().as_secs_f64();
+        println!(
+            "{name:>12}: {seconds:>8.3}s {:>12.0} generations/s {:>8.1} MB/s written",
+            generations as f64 / seconds,
+            bytes as f64 / seconds / 1e6
+        );
+        Ok(())
+    };
+    let buffered = || File::create(&path).map(BufWriter::new);
+    time(
+        "Unbuffered",
+        &mut File::create(&path)?,
+        Sampler::new(1, false),
+    )?;
+    time("Buffered", &mut buffered()?, Sampler::new(1, false))?;
+    time("Every 100", &mut buffered()?, Sampler::new(100, false))?;
+    time("Only final", &mut buffered()?, Sampler::new(1, true))?;
+    std::fs::remove_file(&path)
+}
@@ -0,0 +1,46 @@
+//! Compares `simple::State::next` against `simple::Packed::next`.
+//!
+//! There's no stable `#[bench]`, so this is just a timed loop, run with
+//! `cargo bench --bench simple`. Pass a cell count and generation count to
+//! change the size of the run, e.g. `cargo bench --bench simple -- 1000000 1000`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cellular_automata::simple::{Boundary, Packed, State, Wolfram};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+fn time<S>(name: &str, mut state: S, generations: usize, next: impl Fn(&S) -> S) -> Duration {
+    let start = Instant::now();
+    for _ in 0..generations {
+        state = black_box(next(&state));
+    }
+    let elapsed = start.elapsed();
+    println!("{name:>8}: {elapsed:?}");
+    elapsed
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` along, so skip anything that isn't a number
+    let mut args = std::env::args().skip(1).filter_map(|a| a.parse().ok());
+    let cells: usize = args.next().unwrap_or(100_000);
+    let generations: usize = args.next().unwrap_or(1_000);
+
+    let mut rng = StdRng::seed_from_u64(110);
+    let initial: Vec<u8> = (0..cells).map(|_| rng.gen_range(0..=1)).collect();
+    let initial = State::new(&initial);
+    let rule = Wolfram::from_code(110);
+
+    println!("Rule 110, {cells} cells, {generations} generations");
+    let unpacked = time("State", initial.clone(), generations, |s| {
+        s.next(&rule, Boundary::Periodic)
+    });
+    let packed = time("Packed", Packed::from_state(&initial), generations, |s| {
+        s.next(&rule, Boundary::Periodic)
+    });
+    println!(
+        " speedup: {:.1}x",
+        unpacked.as_secs_f64() / packed.as_secs_f64()
+    );
+}
@@ -0,0 +1,275 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde::Serialize;
+
+use crate::collisions::{CollisionRule, State, Stats};
+
+/**
+ * How one random field turned out
+ *
+ * `finished` is whether it got to where nothing else could collide, rather
+ * than running out of generations.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outcome {
+    pub seed: u64,
+    pub survivors: usize,
+    pub generations: u64,
+    pub mass_destroyed: f64,
+    pub finished: bool,
+}
+
+/**
+ * Runs `state` until nothing else can collide, or for at most `generations`
+ * if that isn't 0
+ */
+pub fn measure(seed: u64, state: State, rule: &dyn CollisionRule, generations: u64) -> Outcome {
+    let mass = state.mass();
+    let (state, generations, finished) = state.settle(rule, generations);
+    Outcome {
+        seed,
+        survivors: Stats::of(&state).objects,
+        generations,
+        mass_destroyed: mass - state.mass(),
+        finished,
+    }
+}
+
+/**
+ * Measures a field for each of `runs` seeds, starting from `first_seed`,
+ * spread over `threads` threads
+ *
+ * `field` makes the state and rule for a seed. It's called on whichever
+ * thread runs that seed, so rules with their own RNG don't need to be shared.
+ * The outcomes are in seed order however the work got split up, so the same
+ * seeds always give the same output. If `field` fails for a seed the other
+ * threads stop taking new ones, and that error is returned.
+ */
+pub fn run<F>(
+    first_seed: u64,
+    runs: u64,
+    threads: usize,
+    generations: u64,
+    field: F,
+) -> Result<Vec<Outcome>, String>
+where
+    F: Fn(u64) -> Result<(State, Box<dyn CollisionRule>), String> + Sync,
+{
+    // Fields can take very different amounts of time, so threads take the
+    // next one when they're ready rather than each getting a share up front
+    let next = AtomicU64::new(0);
+    let failed = AtomicBool::new(false);
+    let outcomes = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| -> Result<(), String> {
+                    loop {
+                        if failed.load(Ordering::Relaxed) {
+                            return Ok(());
+                        }
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= runs {
+                            return Ok(());
+                        }
+                        let seed = first_seed.wrapping_add(i);
+                        let (state, rule) = field(seed).inspect_err(|_| {
+                            failed.store(true, Ordering::Relaxed);
+                        })?;
+                        let outcome = measure(seed, state, rule.as_ref(), generations);
+                        outcomes.lock().unwrap().push((i, outcome));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .try_for_each(|worker| worker.join().unwrap())
+    })?;
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|(i, _)| *i);
+    Ok(outcomes.into_iter().map(|(_, outcome)| outcome).collect())
+}
+
+pub fn write_csv<W: Write>(out: &mut W, outcomes: &[Outcome]) -> io::Result<()> {
+    writeln!(out, "seed,survivors,generations,mass_destroyed,finished")?;
+    for o in outcomes {
+        writeln!(
+            out,
+            "{},{},{},{},{}",
+            o.seed, o.survivors, o.generations, o.mass_destroyed, o.finished
+        )?;
+    }
+    Ok(())
+}
+
+/**
+ * Counts of values in equal sized ranges, drawn as a bar for each range
+ *
+ * When the values are all whole numbers and there aren't more different ones
+ * than there are bins, each bin is a single value.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub title: String,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /**
+     * The lowest value in each bin, and how many there were
+     */
+    pub bins: Vec<(f64, usize)>,
+    width: f64,
+}
+
+/**
+ * How many characters the longest bar is
+ */
+const BAR_WIDTH: usize = 50;
+
+impl Histogram {
+    pub fn new(title: &str, values: &[f64], bins: usize) -> Histogram {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let whole = values.iter().all(|v| v.fract() == 0.0);
+        let bins = bins.max(1);
+        let width = match values.is_empty() {
+            true => 1.0,
+            false if whole && max - min < bins as f64 => 1.0,
+            // Wide enough that the largest value is in the last bin rather
+            // than one of its own
+            false if whole => ((max - min + 1.0) / bins as f64).ceil(),
+            false if max > min => (max - min) / bins as f64,
+            false => 1.0,
+        };
+        let count = match values.is_empty() {
+            true => 0,
+            false => (((max - min) / width) as usize + 1).min(bins),
+        };
+        let mut counts = vec![0; count];
+        for v in values {
+            let bin = (((v - min) / width) as usize).min(count - 1);
+            counts[bin] += 1;
+        }
+        Histogram {
+            title: title.to_string(),
+            min,
+            max,
+            mean,
+            bins: counts
+                .into_iter()
+                .enumerate()
+                .map(|(i, n)| (min + i as f64 * width, n))
+                .collect(),
+            width,
+        }
+    }
+}
+
+impl Display for Histogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.bins.is_empty() {
+            return writeln!(f, "{}: nothing to count", self.title);
+        }
+        writeln!(
+            f,
+            "{} (min {}, mean {:.2}, max {})",
+            self.title, self.min, self.mean, self.max
+        )?;
+        let labels: Vec<String> = self
+            .bins
+            .iter()
+            .map(|&(low, _)| match self.width {
+                1.0 if low.fract() == 0.0 => format!("{low}"),
+                _ if self.min.fract() == 0.0 && self.width.fract() == 0.0 => {
+                    format!("{low}-{}", low + self.width - 1.0)
+                }
+                _ => format!("{:.1}-{:.1}", low, low + self.width),
+            })
+            .collect();
+        let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+        let most = self.bins.iter().map(|&(_, n)| n).max().unwrap_or(0).max(1);
+        for (label, &(_, n)) in labels.iter().zip(&self.bins) {
+            let bar = (n * BAR_WIDTH).div_ceil(most);
+            writeln!(f, "{label:>label_width$} | {} {n}", "#".repeat(bar))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::collisions::Classic;
+
+    use super::*;
+
+    #[test]
+    fn measures_a_field() {
+        let state = State::from_string("+5 _ -3 _ 2 _ +1").unwrap();
+        assert_eq!(
+            measure(7, state.clone(), &Classic::WINNER_TAKES_ALL, 0),
+            Outcome {
+                seed: 7,
+                survivors: 1,
+                generations: 6,
+                mass_destroyed: 6.0,
+                finished: true
+            }
+        );
+        let cut_short = measure(7, state, &Classic::WINNER_TAKES_ALL, 1);
+        assert_eq!((cut_short.generations, cut_short.finished), (1, false));
+    }
+
+    #[test]
+    fn same_seeds_same_outcomes() {
+        let field = |seed: u64| -> Result<(State, Box<dyn CollisionRule>), String> {
+            let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(seed);
+            let state = State::random(60, 20, 20, 20, 40, &mut rng);
+            Ok((state, Box::new(Classic::WINNER_TAKES_ALL)))
+        };
+        let one = run(100, 50, 1, 0, field).unwrap();
+        let many = run(100, 50, 8, 0, field).unwrap();
+        assert_eq!(one, many);
+        assert_eq!(one.len(), 50);
+        assert!(one.iter().map(|o| o.seed).eq(100..150));
+
+        let failing = run(0, 10, 4, 0, |seed| match seed {
+            3 => Err("no".to_string()),
+            _ => field(seed),
+        });
+        assert_eq!(failing, Err("no".to_string()));
+        // Nothing is set aside up front for however many runs are asked for
+        let huge = run(0, u64::MAX, 4, 0, |_| Err("no".to_string()));
+        assert_eq!(huge, Err("no".to_string()));
+        // and the threads that didn't hit the error don't carry on with the rest
+        let one_bad = run(0, u64::MAX, 4, 0, |seed| match seed {
+            3 => Err("no".to_string()),
+            _ => field(seed),
+        });
+        assert_eq!(one_bad, Err("no".to_string()));
+    }
+
+    #[test]
+    fn draws_histograms() {
+        let survivors = Histogram::new("Survivors", &[1.0, 2.0, 2.0, 4.0], 10);
+        assert_eq!(
+            survivors.to_string(),
+            "Survivors (min 1, mean 2.25, max 4)\n\
+             1 | ######################### 1\n\
+             2 | ################################################## 2\n\
+             3 |  0\n\
+             4 | ######################### 1\n"
+        );
+        let wide = Histogram::new("Generations", &[0.0, 5.0, 9.0, 10.0], 2);
+        assert_eq!(wide.bins, [(0.0, 2), (6.0, 2)]);
+        assert!(wide.to_string().contains("\n 0-5 | ##"));
+        let fractions = Histogram::new("Mass", &[0.5, 1.0, 1.5], 2);
+        assert_eq!(fractions.bins, [(0.5, 1), (1.0, 2)]);
+        assert_eq!(Histogram::new("Mass", &[], 2).bins, []);
+        assert_eq!(Histogram::new("Mass", &[3.5, 3.5], 4).bins, [(3.5, 2)]);
+    }
+}
@@ -1,16 +1,91 @@
 use std::fmt::{Display, Write};
+use std::{io, mem};
 
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-pub struct Rule {
+mod error;
+mod fast_forward;
+mod generate;
+pub mod grid;
+mod physics;
+mod speed;
+mod weight;
+
+pub use error::{ParseError, ParseErrorKind};
+pub use generate::{Counts, Distribution, Weights};
+pub use physics::{Elastic, Merge, Physics, Probabilistic};
+use speed::Motion;
+pub use speed::Speed;
+pub use weight::Weight;
+
+/**
+ * The physics of the collider, what happens when things run into each other
+ *
+ * `collide` is called for every move, including moves into empty cells and
+ * into objects going the same way, which should usually give `MovingWon` and
+ * `DestinationEscapes` respectively so things keep moving.
+ */
+pub trait CollisionRule<W: Weight = u32> {
+    fn collide(&self, moving: &Moving<W>, destination: &Cell<W>) -> CollisionResult<W>;
+
+    /**
+     * What an object turns into when it runs into the end of the field
+     */
+    fn hit_wall(&self, moving: &Moving<W>) -> Cell<W>;
+
+    /**
+     * Whether an object that's bumped into a wall takes the place of whatever
+     * bumped it, rather than that running into it where it ends up. The
+     * original physics did this, so `Classic` still does.
+     */
+    fn wall_crushes_bumper(&self) -> bool {
+        false
+    }
+}
+
+/**
+ * Bounces off the wall if `bounce` is set, otherwise stops against it
+ */
+fn hit_wall<W: Weight>(bounce: bool, moving: &Moving<W>) -> Cell<W> {
+    match (bounce, moving) {
+        (true, Moving::Left(w)) => Cell::InMotion(Moving::Right(*w)),
+        (true, Moving::Right(w)) => Cell::InMotion(Moving::Left(*w)),
+        (false, Moving::Left(w) | Moving::Right(w)) => Cell::Stationary(*w),
+    }
+}
+
+/**
+ * The original physics, where the heavier object wins a collision and either
+ * comes out of it unscathed or loses the weight of the lighter one
+ */
+pub struct Classic {
     pub bounce: bool,
     pub partial_destroy: bool,
 }
-impl Rule {
+impl Classic {
+    /**
+     * The physics from the original challenge, no bouncing and the loser is
+     * destroyed outright
+     */
+    pub const WINNER_TAKES_ALL: Classic = Classic {
+        bounce: false,
+        partial_destroy: false,
+    };
+}
+impl<W: Weight> CollisionRule<W> for Classic {
+    fn hit_wall(&self, moving: &Moving<W>) -> Cell<W> {
+        hit_wall(self.bounce, moving)
+    }
+
+    fn wall_crushes_bumper(&self) -> bool {
+        true
+    }
+
     /**
      * Resolve collisions
      */
-    pub fn collide(&self, moving: &Moving, destination: &Cell) -> CollisionResult {
+    fn collide(&self, moving: &Moving<W>, destination: &Cell<W>) -> CollisionResult<W> {
         match (moving, destination) {
             // Uncontested moves
             (m @ Moving::Left(_), Cell::Empty) => CollisionResult::MovingWon(m.clone()),
@@ -36,9 +111,9 @@ impl Rule {
             // without creating more duplication than I'd be removing.
             (Moving::Left(m), Cell::InMotion(Moving::Right(d))) => {
                 let new_weight = if self.partial_destroy {
-                    (m - d).abs()
+                    m.difference(*d)
                 } else {
-                    *m.max(d)
+                    heavier(*m, *d)
                 };
                 if m == d {
                     CollisionResult::BothDestroyed
@@ -50,9 +125,9 @@ impl Rule {
             }
             (Moving::Right(m), Cell::InMotion(Moving::Left(d))) => {
                 let new_weight = if self.partial_destroy {
-                    (m - d).abs()
+                    m.difference(*d)
                 } else {
-                    *m.max(d)
+                    heavier(*m, *d)
                 };
                 if m == d {
                     CollisionResult::BothDestroyed
@@ -64,9 +139,9 @@ impl Rule {
             }
             (Moving::Left(m), Cell::Stationary(s)) => {
                 let new_weight = if self.partial_destroy {
-                    (m - s).abs()
+                    m.difference(*s)
                 } else {
-                    *m.max(s)
+                    heavier(*m, *s)
                 };
                 if m == s {
                     CollisionResult::BothDestroyed
@@ -78,9 +153,9 @@ impl Rule {
             }
             (Moving::Right(m), Cell::Stationary(s)) => {
                 let new_weight = if self.partial_destroy {
-                    (m - s).abs()
+                    m.difference(*s)
                 } else {
-                    *m.max(s)
+                    heavier(*m, *s)
                 };
                 if m == s {
                     CollisionResult::BothDestroyed
@@ -94,157 +169,505 @@ impl Rule {
     }
 }
 
-#[derive(Debug)]
-pub enum CollisionResult {
+/**
+ * `PartialOrd` doesn't have a `max`, and weights are never NaN anyway
+ */
+fn heavier<W: Weight>(a: W, b: W) -> W {
+    if a > b { a } else { b }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollisionResult<W = u32> {
     BothDestroyed,
-    MovingWon(Moving),
-    DestinationWon(Cell),
+    MovingWon(Moving<W>),
+    DestinationWon(Cell<W>),
     DestinationEscapes {
         // Only the weight of the bumped, because otherwise the
         // types get weird and we already know the direction from context
-        bumped: i8,
-        moved: Moving,
+        bumped: W,
+        moved: Moving<W>,
     },
+    /**
+     * Neither object is destroyed or moves, they just both change into new
+     * cells where they are
+     */
+    Rebound {
+        moving: Cell<W>,
+        destination: Cell<W>,
+    },
+}
+
+/**
+ * A single call to `CollisionRule::collide`, including the uncontested moves into
+ * empty cells
+ *
+ * `generation` is the one being worked out, so the first step is generation
+ * 1, and `index` is where the moving object was moving from.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event<W = u32> {
+    pub generation: usize,
+    pub index: usize,
+    pub moving: Moving<W>,
+    pub destination: Cell<W>,
+    pub result: CollisionResult<W>,
+}
+
+/**
+ * Gets told about everything that happens while working out a generation
+ *
+ * Closures taking an `&Event` work as observers, and so does a `Vec<Event>`,
+ * which just collects them. A pair of observers passes everything on to both.
+ */
+pub trait Observer<W: Weight = u32> {
+    fn collided(&mut self, event: &Event<W>);
+
+    /**
+     * Called once the generation is done, with the states before and after
+     */
+    fn finished(&mut self, _before: &State<W>, _after: &State<W>) {}
+}
+
+impl<W: Weight, F: FnMut(&Event<W>)> Observer<W> for F {
+    fn collided(&mut self, event: &Event<W>) {
+        self(event)
+    }
+}
+
+impl<W: Weight> Observer<W> for Vec<Event<W>> {
+    fn collided(&mut self, event: &Event<W>) {
+        self.push(event.clone());
+    }
+}
+
+impl<W: Weight, A: Observer<W> + ?Sized, B: Observer<W> + ?Sized> Observer<W> for (&mut A, &mut B) {
+    fn collided(&mut self, event: &Event<W>) {
+        self.0.collided(event);
+        self.1.collided(event);
+    }
+
+    fn finished(&mut self, before: &State<W>, after: &State<W>) {
+        self.0.finished(before, after);
+        self.1.finished(before, after);
+    }
+}
+
+/**
+ * What happened over a generation
+ *
+ * The collision counts only include moves into a cell that already had
+ * something in it, everything else is a snapshot of the state at the end of
+ * the generation. The momentum in each direction is the weight of everything
+ * moving that way times how many cells a generation it goes.
+ */
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub both_destroyed: usize,
+    pub moving_won: usize,
+    pub destination_won: usize,
+    pub destination_escapes: usize,
+    pub rebounds: usize,
+    pub mass_destroyed: f64,
+    pub momentum_left: f64,
+    pub momentum_right: f64,
+    pub objects: usize,
+}
+
+impl Stats {
+    /**
+     * Just the snapshot half of the stats, with no collisions
+     */
+    pub fn of<W: Weight>(state: &State<W>) -> Stats {
+        let mut stats = Stats::default();
+        stats.snapshot(state);
+        stats
+    }
+
+    fn snapshot<W: Weight>(&mut self, state: &State<W>) {
+        self.momentum_left = 0.0;
+        self.momentum_right = 0.0;
+        self.objects = 0;
+        for (cell, motion) in state.cells.iter().zip(&state.motion) {
+            let momentum = |w: &W| w.to_f64() * motion.speed.cells_per_generation();
+            match cell {
+                Cell::Empty => continue,
+                Cell::Stationary(_) => (),
+                Cell::InMotion(Moving::Left(w)) => self.momentum_left += momentum(w),
+                Cell::InMotion(Moving::Right(w)) => self.momentum_right += momentum(w),
+            }
+            self.objects += 1;
+        }
+    }
+
+    pub fn collisions(&self) -> usize {
+        self.both_destroyed
+            + self.moving_won
+            + self.destination_won
+            + self.destination_escapes
+            + self.rebounds
+    }
+
+    /**
+     * Adds up the collisions and mass destroyed over a run, and takes the
+     * snapshot from `generation`, so feeding this every generation in order
+     * gives the totals for the whole run
+     */
+    pub fn accumulate(&mut self, generation: &Stats) {
+        self.both_destroyed += generation.both_destroyed;
+        self.moving_won += generation.moving_won;
+        self.destination_won += generation.destination_won;
+        self.destination_escapes += generation.destination_escapes;
+        self.rebounds += generation.rebounds;
+        self.mass_destroyed += generation.mass_destroyed;
+        self.momentum_left = generation.momentum_left;
+        self.momentum_right = generation.momentum_right;
+        self.objects = generation.objects;
+    }
+}
+
+/**
+ * Counts up the collisions for a single generation, so this wants a fresh
+ * `Stats` each time
+ */
+impl<W: Weight> Observer<W> for Stats {
+    fn collided(&mut self, event: &Event<W>) {
+        if event.destination == Cell::Empty {
+            return;
+        }
+        match event.result {
+            CollisionResult::BothDestroyed => self.both_destroyed += 1,
+            CollisionResult::MovingWon(_) => self.moving_won += 1,
+            CollisionResult::DestinationWon(_) => self.destination_won += 1,
+            CollisionResult::DestinationEscapes { .. } => self.destination_escapes += 1,
+            CollisionResult::Rebound { .. } => self.rebounds += 1,
+        }
+    }
+
+    fn finished(&mut self, before: &State<W>, after: &State<W>) {
+        self.snapshot(after);
+        self.mass_destroyed = before.mass() - after.mass();
+    }
+}
+
+/**
+ * One row per generation, numbered from 0
+ */
+pub fn write_stats_csv<W: io::Write>(out: &mut W, stats: &[Stats]) -> io::Result<()> {
+    let mut csv = StatsCsv::new(out)?;
+    for s in stats {
+        csv.write(s)?;
+    }
+    Ok(())
+}
+
+/**
+ * The same as `write_stats_csv`, a generation at a time, so a long run doesn't
+ * have to keep the stats for all of them
+ */
+pub struct StatsCsv<W: io::Write> {
+    out: W,
+    generation: usize,
+}
+
+impl<W: io::Write> StatsCsv<W> {
+    /**
+     * Writes the header straight away
+     */
+    pub fn new(mut out: W) -> io::Result<StatsCsv<W>> {
+        writeln!(
+            out,
+            "generation,both_destroyed,moving_won,destination_won,destination_escapes,rebounds,mass_destroyed,momentum_left,momentum_right,objects"
+        )?;
+        Ok(StatsCsv { out, generation: 0 })
+    }
+
+    pub fn write(&mut self, s: &Stats) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.generation,
+            s.both_destroyed,
+            s.moving_won,
+            s.destination_won,
+            s.destination_escapes,
+            s.rebounds,
+            s.mass_destroyed,
+            s.momentum_left,
+            s.momentum_right,
+            s.objects
+        )?;
+        self.generation += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
 }
 
 // Split these out because otherwise the types were getting real messy
-#[derive(Debug, Clone)]
-pub enum Moving {
-    Left(i8),
-    Right(i8),
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Moving<W = u32> {
+    Left(W),
+    Right(W),
 }
 
-#[derive(Debug, Clone)]
-pub enum Cell {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cell<W = u32> {
     Empty,
-    Stationary(i8),
-    InMotion(Moving),
+    Stationary(W),
+    InMotion(Moving<W>),
+}
+
+/**
+ * What's past the ends of the field
+ */
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Topology {
+    /// Walls, which objects stop at or bounce off depending on the rule
+    #[default]
+    Walls,
+    /// The ends are joined, so things leaving one end come back in at the other
+    Ring,
+}
+
+/**
+ * Saves passing the generation all the way down through `move_left` and
+ * `move_right`
+ *
+ * On a ring the cells get rotated around before moving anything, see
+ * `State::cut`, so `offset` is where index 0 really is.
+ */
+struct Observed<'a, W: Weight> {
+    generation: usize,
+    offset: usize,
+    len: usize,
+    observer: &'a mut dyn Observer<W>,
 }
 
+impl<W: Weight> Observed<'_, W> {
+    fn collided(
+        &mut self,
+        index: usize,
+        moving: Moving<W>,
+        destination: Cell<W>,
+        result: &CollisionResult<W>,
+    ) {
+        self.observer.collided(&Event {
+            generation: self.generation,
+            index: (index + self.offset) % self.len,
+            moving,
+            destination,
+            result: result.clone(),
+        });
+    }
+}
+
+/**
+ * `motion` lines up with `cells`, and is just left at the default for empty
+ * cells
+ */
 #[derive(Debug, Clone)]
-pub struct State {
-    cells: Vec<Cell>,
+pub struct State<W = u32> {
+    cells: Vec<Cell<W>>,
+    motion: Vec<Motion>,
+    topology: Topology,
 }
 
 struct ParserState {
     is_left: bool,
     is_right: bool,
     curr: Vec<char>,
+    speed: Option<Vec<char>>,
+    /**
+     * Where in the string the cell being parsed started
+     */
+    start: usize,
 }
 impl ParserState {
-    fn reset(&mut self) {
+    fn reset(&mut self, start: usize) {
         self.is_left = false;
         self.is_right = false;
         self.curr = Vec::new();
+        self.speed = None;
+        self.start = start;
     }
-    fn parse(&self) -> Result<Option<Cell>, String> {
+    fn parse<W: Weight>(&self) -> Result<Option<(Cell<W>, Speed)>, ParseErrorKind> {
         if !self.curr.is_empty() {
             let raw_str: String = self.curr.iter().collect();
             let value = raw_str
-                .parse::<i8>()
-                .map_err(|e| format!("Invalid number ({raw_str}): {e}"))?;
-            if value > 99 {
-                Err("Value must be between 0 and 99".to_string())
+                .parse::<W>()
+                .map_err(|e| ParseErrorKind::InvalidWeight {
+                    raw: raw_str.clone(),
+                    reason: e.to_string(),
+                })?;
+            let speed = match &self.speed {
+                Some(raw_speed) => raw_speed
+                    .iter()
+                    .collect::<String>()
+                    .parse()
+                    .map_err(ParseErrorKind::InvalidSpeed)?,
+                None => Speed::default(),
+            };
+            if !value.is_valid() {
+                Err(ParseErrorKind::WeightOutOfRange {
+                    max: W::MAX.to_string(),
+                })
             } else if self.is_left && self.is_right {
-                Err("Only one prefix is allowed".to_string())
+                Err(ParseErrorKind::TwoSigns)
             } else if self.is_left {
-                Ok(Some(Cell::InMotion(Moving::Left(value))))
+                Ok(Some((Cell::InMotion(Moving::Left(value)), speed)))
             } else if self.is_right {
-                Ok(Some(Cell::InMotion(Moving::Right(value))))
+                Ok(Some((Cell::InMotion(Moving::Right(value)), speed)))
             } else {
-                Ok(Some(Cell::Stationary(value)))
+                Ok(Some((Cell::Stationary(value), speed)))
             }
+        } else if self.speed.is_some() {
+            Err(ParseErrorKind::SpeedWithoutWeight)
         } else {
             Ok(None)
         }
     }
 }
 
-impl State {
+impl<W: Weight> State<W> {
     /**
      * Generates a random initial state using a weighted-probability algorithm
+     *
+     * Everything comes from `r`, so a seeded RNG always gives the same state.
      */
-    pub fn random(
+    pub fn random<R: Rng>(
         length: usize,
         weight_stationary: u16,
         weight_left: u16,
         weight_right: u16,
         weight_empty: u16,
-    ) -> State {
-        let mut r = rand::thread_rng();
-
-        // Weighted random alg stolen from https://stackoverflow.com/a/8435261
-        let total_weight = weight_stationary + weight_left + weight_right + weight_empty;
-        let spec_stationary = weight_stationary;
-        let spec_left = spec_stationary + weight_left;
-        let spec_right = spec_left + weight_right;
-
-        let cells: Vec<Cell> = (0..length)
-            .map(|_| {
-                let choice = r.gen_range(0..=total_weight);
-                if choice < spec_stationary {
-                    Cell::Stationary(r.gen_range(1..100))
-                } else if choice < spec_left {
-                    Cell::InMotion(Moving::Left(r.gen_range(1..100)))
-                } else if choice < spec_right {
-                    Cell::InMotion(Moving::Right(r.gen_range(1..100)))
-                } else {
-                    Cell::Empty
-                }
-            })
-            .collect();
-        State { cells }
+        r: &mut R,
+    ) -> State<W> {
+        State::random_with(
+            length,
+            weight_stationary,
+            weight_left,
+            weight_right,
+            weight_empty,
+            &Weights::default(),
+            r,
+        )
+    }
+
+    /**
+     * Everything starts off at one cell a generation
+     */
+    fn new(cells: Vec<Cell<W>>) -> State<W> {
+        let motion = vec![Motion::default(); cells.len()];
+        State {
+            cells,
+            motion,
+            topology: Topology::Walls,
+        }
     }
 
     /**
      * Very dumb parsing, nothing to see here
+     *
+     * A cell can end with `@` and a speed, like `+3@2` for something going
+     * two cells a generation, or `-3@1/4` for one cell every four
+     * generations. How far along a slow object is isn't part of the string,
+     * so it always starts from scratch.
      */
-    pub fn from_string(raw: &str) -> Result<State, String> {
+    pub fn from_string(raw: &str) -> Result<State<W>, ParseError> {
+        // Offsets are into `raw`, not the trimmed version
+        let skipped = raw.len() - raw.trim_start().len();
         let mut state = ParserState {
             is_left: false,
             is_right: false,
             curr: Vec::new(),
+            speed: None,
+            start: skipped,
         };
-        let mut cells: Vec<Cell> = Vec::new();
+        let mut parsed: Vec<(Cell<W>, Speed)> = Vec::new();
+        let error = |kind, offset, cell| ParseError { kind, offset, cell };
 
-        for c in raw.trim().chars() {
+        for (offset, c) in raw.trim().char_indices() {
+            let offset = offset + skipped;
+            let cell = parsed.len();
             match c {
                 ' ' => {
-                    if let Some(cell) = state.parse()? {
-                        cells.push(cell);
-                    }
-                    state.reset();
+                    parsed.extend(state.parse().map_err(|e| error(e, state.start, cell))?);
+                    state.reset(offset + 1);
                 }
                 '-' => {
                     if state.curr.is_empty() {
                         state.is_left = true;
                     } else {
-                        return Err("'-' must be at the start of a cell".to_string());
+                        return Err(error(ParseErrorKind::MisplacedSign(c), offset, cell));
                     }
                 }
                 '+' => {
                     if state.curr.is_empty() {
                         state.is_right = true;
                     } else {
-                        return Err("'+' must be at the start of a cell".to_string());
+                        return Err(error(ParseErrorKind::MisplacedSign(c), offset, cell));
                     }
                 }
-                _ if c.is_ascii_digit() => {
-                    state.curr.push(c);
+                '@' => {
+                    if state.speed.is_some() {
+                        return Err(error(ParseErrorKind::TwoSpeeds, offset, cell));
+                    }
+                    state.speed = Some(Vec::new());
                 }
+                _ if c.is_ascii_digit() || c == '/' || c == '.' => match &mut state.speed {
+                    Some(speed) if c != '.' => speed.push(c),
+                    Some(_) => return Err(error(ParseErrorKind::DotOutsideWeight, offset, cell)),
+                    None if c == '/' => {
+                        return Err(error(ParseErrorKind::SlashOutsideSpeed, offset, cell));
+                    }
+                    None => state.curr.push(c),
+                },
                 '_' => {
-                    if !state.curr.is_empty() {
-                        return Err("'_' must be separated from other cells by a space".to_string());
+                    if !state.curr.is_empty() || state.speed.is_some() {
+                        return Err(error(ParseErrorKind::UnseparatedEmpty, offset, cell));
                     }
-                    cells.push(Cell::Empty);
+                    parsed.push((Cell::Empty, Speed::default()));
                 }
-                _ => return Err(format!("Unexpected character: '{c}'")),
+                _ => return Err(error(ParseErrorKind::UnexpectedCharacter(c), offset, cell)),
             };
         }
-        if let Some(cell) = state.parse()? {
-            cells.push(cell);
-        }
-        Ok(State { cells })
+        let cell = parsed.len();
+        parsed.extend(state.parse().map_err(|e| error(e, state.start, cell))?);
+        let (cells, speeds): (Vec<Cell<W>>, Vec<Speed>) = parsed.into_iter().unzip();
+        Ok(State {
+            cells,
+            motion: speeds.into_iter().map(Motion::new).collect(),
+            topology: Topology::Walls,
+        })
+    }
+
+    pub fn cells(&self) -> &[Cell<W>] {
+        &self.cells
+    }
+
+    /**
+     * Fields start off with walls at the ends
+     */
+    pub fn with_topology(mut self, topology: Topology) -> State<W> {
+        self.topology = topology;
+        self
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /**
+     * How fast whatever's in `index` goes, which is only interesting for
+     * objects
+     */
+    pub fn speed(&self, index: usize) -> Speed {
+        self.motion[index].speed
     }
 
     /**
@@ -273,55 +696,59 @@ impl State {
      * Move an object leftwards
      *
      * Depending on how collisions resolve, it can recursively shuffle objects
-     * further leftward. `was_bumped` is important for this, so we don't accidentally
-     * overwrite the value that bumped this one.
+     * further leftward. Anything that gets bumped is moved out of the way
+     * first, and then this one tries again with whatever's left in the cell,
+     * unless the rule says it's crushed by bumping something into the wall.
      */
     fn move_left(
-        cells: &mut Vec<Cell>,
+        &mut self,
         index: usize,
-        weight: i8,
-        was_bumped: bool,
-        rule: &Rule,
+        weight: W,
+        rule: &dyn CollisionRule<W>,
+        observer: &mut Observed<W>,
     ) -> usize {
-        // The index check is because `usize` is unsigned and underflow sucks
-        match if index == 0 {
-            None
-        } else {
-            // Have to clone here or the borrow checker gets annoyed when we
-            // mutate things later - but oddly only after pulling the code
-            // that sets the cell to empty was pulled out to before the match.
-            // If it's inlined with each setting of the previous cell, the
-            // borrow checker is fine with it.
-            cells.get(index - 1).cloned()
-        } {
-            None => {
-                // Hit a side wall, maybe bounce?
-                if rule.bounce {
-                    cells[index] = Cell::InMotion(Moving::Right(weight));
-                } else {
-                    cells[index] = Cell::Stationary(weight);
-                }
+        let moving = Moving::Left(weight);
+        // The index check is because `usize` is unsigned and underflow sucks.
+        // Have to clone here or the borrow checker gets annoyed when we
+        // mutate things later.
+        let Some(destination) = index
+            .checked_sub(1)
+            .and_then(|i| self.cells.get(i).cloned())
+        else {
+            // Hit a side wall
+            self.cells[index] = rule.hit_wall(&moving);
+            return index + 1;
+        };
+        let motion = self.take(index);
+        let result = rule.collide(&moving, &destination);
+        observer.collided(index, moving, destination, &result);
+        match result {
+            CollisionResult::BothDestroyed => {
+                self.take(index - 1);
             }
-            Some(destination) => {
-                if !was_bumped {
-                    cells[index] = Cell::Empty;
-                }
-                match rule.collide(&Moving::Left(weight), &destination) {
-                    CollisionResult::BothDestroyed => {
-                        cells[index - 1] = Cell::Empty;
-                    }
-                    CollisionResult::MovingWon(cell) => {
-                        cells[index - 1] = Cell::InMotion(cell);
-                    }
-                    CollisionResult::DestinationWon(cell) => {
-                        cells[index - 1] = cell;
-                    }
-                    CollisionResult::DestinationEscapes { moved, bumped } => {
-                        cells[index - 1] = Cell::InMotion(moved);
-                        // Need to propagate the motion backwards
-                        State::move_left(cells, index - 1, bumped, true, rule);
-                    }
+            CollisionResult::MovingWon(cell) => {
+                self.put(index - 1, Cell::InMotion(cell), motion);
+            }
+            CollisionResult::DestinationWon(cell) => {
+                self.cells[index - 1] = cell;
+            }
+            CollisionResult::Rebound {
+                moving,
+                destination,
+            } => {
+                self.put(index, moving, motion);
+                self.cells[index - 1] = destination;
+            }
+            CollisionResult::DestinationEscapes { moved, bumped } => {
+                // Need to propagate the motion backwards, and then see
+                // if there's room to move in after it
+                self.put(index, Cell::InMotion(moved.clone()), motion);
+                self.move_left(index - 1, bumped, rule, observer);
+                if index == 1 && rule.wall_crushes_bumper() {
+                    self.take(index);
+                    return index + 1;
                 }
+                return self.move_object(index, moved, rule, observer);
             }
         }
         // This one's simple, since we're moving the object into an already
@@ -334,90 +761,250 @@ impl State {
      * Move an object rightwards
      *
      * Depending on how collisions resolve, it can recursively shuffle objects
-     * further rightward. `was_bumped` is important for this, so we don't accidentally
-     * overwrite the value that bumped this one.
+     * further rightward, the same way as `move_left`.
      */
     fn move_right(
-        cells: &mut Vec<Cell>,
+        &mut self,
         index: usize,
-        weight: i8,
-        was_bumped: bool,
-        rule: &Rule,
+        weight: W,
+        rule: &dyn CollisionRule<W>,
+        observer: &mut Observed<W>,
     ) -> usize {
+        let moving = Moving::Right(weight);
         // Cloning shenanigans again, see `move_left`
-        match cells.get(index + 1).cloned() {
-            None => {
-                // Hit a side wall, maybe bounce?
-                if rule.bounce {
-                    cells[index] = Cell::InMotion(Moving::Left(weight));
-                } else {
-                    cells[index] = Cell::Stationary(weight);
-                }
-                // Since this only affected the current cell, we only
-                // advance once (though this is kind of irrelevant since we're
-                // advancing past the end of the vector)
+        let Some(destination) = self.cells.get(index + 1).cloned() else {
+            // Hit a side wall. Since this only affected the current cell, we
+            // only advance once (though this is kind of irrelevant since
+            // we're advancing past the end of the vector)
+            self.cells[index] = rule.hit_wall(&moving);
+            return index + 1;
+        };
+        let motion = self.take(index);
+        let result = rule.collide(&moving, &destination);
+        observer.collided(index, moving, destination, &result);
+        match result {
+            CollisionResult::BothDestroyed => {
+                self.take(index + 1);
+                // Since we destroyed the next cell, we can skip it
+                // as well
+                index + 2
+            }
+            CollisionResult::MovingWon(cell) => {
+                self.put(index + 1, Cell::InMotion(cell), motion);
+                // We moved into the next cell, so it's already been
+                // processed and we can skip it as well
+                index + 2
+            }
+            CollisionResult::DestinationWon(cell) => {
+                self.cells[index + 1] = cell;
+                // We updated the next cell, but it hasn't been processed
+                // so we can't skip it
                 index + 1
             }
-            Some(destination) => {
-                if !was_bumped {
-                    cells[index] = Cell::Empty;
-                }
-                match rule.collide(&Moving::Right(weight), &destination) {
-                    CollisionResult::BothDestroyed => {
-                        cells[index + 1] = Cell::Empty;
-                        // Since we destroyed the next cell, we can skip it
-                        // as well
-                        index + 2
-                    }
-                    CollisionResult::MovingWon(cell) => {
-                        cells[index + 1] = Cell::InMotion(cell);
-                        // We moved into the next cell, so it's already been
-                        // processed and we can skip it as well
-                        index + 2
-                    }
-                    CollisionResult::DestinationWon(cell) => {
-                        cells[index + 1] = cell;
-                        // We updated the next cell, but it hasn't been processed
-                        // so we can't skip it
-                        index + 1
-                    }
-                    CollisionResult::DestinationEscapes { moved, bumped } => {
-                        cells[index + 1] = Cell::InMotion(moved);
-                        // Need to propagate the bumped object forwards, so we're
-                        // delegating the amount to change index by to the recursive calls.
-                        // This wasn't needed in `move_left`, but we're moving into unprocessed
-                        // territory and don't want to process objects twice.
-                        State::move_right(cells, index + 1, bumped, true, rule)
-                    }
+            CollisionResult::Rebound {
+                moving,
+                destination,
+            } => {
+                self.put(index, moving, motion);
+                self.cells[index + 1] = destination;
+                // The next cell has had its turn by being hit
+                index + 2
+            }
+            CollisionResult::DestinationEscapes { moved, bumped } => {
+                // Need to propagate the bumped object forwards first, which
+                // can go on to process things further along. This wasn't
+                // needed in `move_left`, but we're moving into unprocessed
+                // territory and don't want to process objects twice.
+                self.put(index, Cell::InMotion(moved.clone()), motion);
+                let bumped_done = self.move_right(index + 1, bumped, rule, observer);
+                if index + 2 == self.cells.len() && rule.wall_crushes_bumper() {
+                    self.take(index);
+                    return bumped_done;
                 }
+                let done = self.move_object(index, moved, rule, observer);
+                bumped_done.max(done)
             }
         }
     }
 
-    pub fn next(&self, rule: &Rule) -> Option<State> {
-        // Stop once collisions are impossible
+    /**
+     * Empties a cell, handing back the motion of whatever was in it
+     */
+    fn take(&mut self, index: usize) -> Motion {
+        self.cells[index] = Cell::Empty;
+        mem::take(&mut self.motion[index])
+    }
+
+    fn put(&mut self, index: usize, cell: Cell<W>, motion: Motion) {
+        self.cells[index] = cell;
+        self.motion[index] = motion;
+    }
+
+    fn move_object(
+        &mut self,
+        index: usize,
+        moving: Moving<W>,
+        rule: &dyn CollisionRule<W>,
+        observer: &mut Observed<W>,
+    ) -> usize {
+        match moving {
+            Moving::Left(weight) => self.move_left(index, weight, rule, observer),
+            Moving::Right(weight) => self.move_right(index, weight, rule, observer),
+        }
+    }
+
+    pub fn next(&self, rule: &dyn CollisionRule<W>) -> Option<State<W>> {
+        self.next_with_stats(rule).map(|(state, _)| state)
+    }
+
+    /**
+     * Same as `next`, but also says what happened along the way
+     */
+    pub fn next_with_stats(&self, rule: &dyn CollisionRule<W>) -> Option<(State<W>, Stats)> {
+        let mut stats = Stats::default();
+        let next = self.next_observed(rule, 0, &mut stats)?;
+        Some((next, stats))
+    }
+
+    /**
+     * Runs until nothing else can collide, or for at most `generations` if
+     * that isn't 0. Gives the last state, how many generations it took, and
+     * whether it got to the end rather than running out of generations.
+     */
+    pub fn settle(self, rule: &dyn CollisionRule<W>, generations: u64) -> (State<W>, u64, bool) {
+        let mut state = self;
+        let mut run = 0;
+        let finished = loop {
+            if generations != 0 && run == generations {
+                break false;
+            }
+            match state.next(rule) {
+                Some(next) => state = next,
+                None => break true,
+            }
+            run += 1;
+        };
+        (state, run, finished)
+    }
+
+    /**
+     * Same as `next`, but tells `observer` about every collision along the
+     * way, `generation` is just passed on to the events
+     */
+    pub fn next_observed(
+        &self,
+        rule: &dyn CollisionRule<W>,
+        generation: usize,
+        observer: &mut dyn Observer<W>,
+    ) -> Option<State<W>> {
+        // Stop once collisions are impossible. This works on a ring too, since
+        // everything going the same way just goes round and round.
         if self.all_empty_or_left() || self.all_empty_or_right() || self.all_empty_or_stationary() {
             return None;
         };
-        let mut index = 0;
-        let mut cells = self.cells.clone();
-        while let Some(cell) = cells.get(index).cloned() {
-            match cell {
-                Cell::Empty => index += 1,
-                Cell::Stationary(_) => index += 1,
-                Cell::InMotion(Moving::Left(weight)) => {
-                    index = State::move_left(&mut cells, index, weight, false, rule);
-                }
-                Cell::InMotion(Moving::Right(weight)) => {
-                    index = State::move_right(&mut cells, index, weight, false, rule);
+        let mut observed = Observed {
+            generation,
+            offset: 0,
+            len: self.cells.len(),
+            observer,
+        };
+        let mut next = self.clone();
+        for (cell, motion) in next.cells.iter().zip(&mut next.motion) {
+            motion.start_generation(matches!(cell, Cell::InMotion(_)));
+        }
+        // Only the objects due a move in each sub-step go, so anything
+        // waiting its turn is in the way like a stationary object would be
+        let sub_steps = next
+            .cells
+            .iter()
+            .zip(&next.motion)
+            .filter(|(cell, _)| matches!(cell, Cell::InMotion(_)))
+            .map(|(_, motion)| motion.moves)
+            .max()
+            .unwrap_or(0);
+        for sub_step in 1..=sub_steps {
+            let offset = match next.topology {
+                Topology::Walls => 0,
+                Topology::Ring => match next.cut() {
+                    Some(offset) => offset,
+                    None => {
+                        next.turn();
+                        continue;
+                    }
+                },
+            };
+            next.cells.rotate_left(offset);
+            next.motion.rotate_left(offset);
+            observed.offset = offset;
+            let mut index = 0;
+            while let Some(cell) = next.cells.get(index).cloned() {
+                match cell {
+                    Cell::InMotion(moving)
+                        if next.motion[index].moves_during(sub_step, sub_steps) =>
+                    {
+                        index = next.move_object(index, moving, rule, &mut observed);
+                    }
+                    _ => index += 1,
                 }
             }
+            next.cells.rotate_right(offset);
+            next.motion.rotate_right(offset);
+        }
+        observer.finished(self, &next);
+        Some(next)
+    }
+
+    /**
+     * Somewhere to cut a ring open so it can be moved like a field with
+     * walls, given as the cell that should go first
+     *
+     * Nothing can cross the gap between a cell that isn't going right and one
+     * after it that isn't going left, so when the ring is cut there neither
+     * end ever gets to the walls. Bumped objects can only be shoved along by
+     * something going their way, so even long chains of them stop before the
+     * cut. The only rings without a gap like that are packed full of objects
+     * all going the same way.
+     */
+    fn cut(&self) -> Option<usize> {
+        let len = self.cells.len();
+        (0..len).map(|i| (i + 1) % len).find(|&after| {
+            let before = (after + len - 1) % len;
+            !matches!(self.cells[before], Cell::InMotion(Moving::Right(_)))
+                && !matches!(self.cells[after], Cell::InMotion(Moving::Left(_)))
+        })
+    }
+
+    /**
+     * Moves everything in a ring with no gaps along one cell, which is all
+     * they can do
+     */
+    fn turn(&mut self) {
+        if let Some(Cell::InMotion(Moving::Right(_))) = self.cells.first() {
+            self.cells.rotate_right(1);
+            self.motion.rotate_right(1);
+        } else {
+            self.cells.rotate_left(1);
+            self.motion.rotate_left(1);
         }
-        Some(State { cells })
+    }
+
+    /**
+     * Total weight of everything in the field
+     */
+    pub fn mass(&self) -> f64 {
+        self.cells
+            .iter()
+            .map(|cell| match cell {
+                Cell::Empty => 0.0,
+                Cell::Stationary(w) | Cell::InMotion(Moving::Left(w) | Moving::Right(w)) => {
+                    w.to_f64()
+                }
+            })
+            .sum()
     }
 }
 
-impl Display for State {
+impl<W: Weight> Display for State<W> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_char('|')?;
         for c in &self.cells {
@@ -438,17 +1025,290 @@ impl Display for State {
  *
  * Make sure that this outputs something that State::from_string can understand
  */
-pub struct DebugOutput<'a>(pub &'a State);
-impl<'a> Display for DebugOutput<'a> {
+pub struct DebugOutput<'a, W = u32>(pub &'a State<W>);
+impl<W: Weight> Display for DebugOutput<'_, W> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for c in &self.0.cells {
+        for (index, c) in self.0.cells.iter().enumerate() {
             match c {
-                Cell::Empty => write!(f, "_ "),
-                Cell::Stationary(w) => write!(f, "{w} "),
-                Cell::InMotion(Moving::Left(w)) => write!(f, "-{w} "),
-                Cell::InMotion(Moving::Right(w)) => write!(f, "+{w} "),
+                Cell::Empty => write!(f, "_"),
+                Cell::Stationary(w) => write!(f, "{w}"),
+                Cell::InMotion(Moving::Left(w)) => write!(f, "-{w}"),
+                Cell::InMotion(Moving::Right(w)) => write!(f, "+{w}"),
             }?;
+            let speed = self.0.speed(index);
+            if *c != Cell::Empty && speed != Speed::default() {
+                write!(f, "@{speed}")?;
+            }
+            f.write_char(' ')?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(raw: &str, partial_destroy: bool) -> (State, Stats) {
+        let rule = Classic {
+            bounce: false,
+            partial_destroy,
+        };
+        State::from_string(raw)
+            .unwrap()
+            .next_with_stats(&rule)
+            .unwrap()
+    }
+
+    #[test]
+    fn counts_collisions() {
+        let (state, stats) = step("+5 _ -3", false);
+        assert_eq!(DebugOutput(&state).to_string(), "_ +5 _ ");
+        assert_eq!(
+            stats,
+            Stats {
+                destination_won: 1,
+                mass_destroyed: 3.0,
+                momentum_right: 5.0,
+                objects: 1,
+                ..Stats::default()
+            }
+        );
+
+        let (_, stats) = step("+5 _ -3", true);
+        assert_eq!(stats.mass_destroyed, 6.0);
+        assert_eq!(stats.momentum_right, 2.0);
+
+        let (_, stats) = step("+4 -4 _ 7 -2", false);
+        assert_eq!(stats.both_destroyed, 1);
+        assert_eq!(stats.destination_won, 1);
+        assert_eq!(stats.collisions(), 2);
+        assert_eq!(stats.mass_destroyed, 10.0);
+        assert_eq!(stats.objects, 1);
+    }
+
+    #[test]
+    fn bumping_into_a_wall_crushes_the_bumper() {
+        let (state, _) = step("+8 -6 _ +5 +7 +9", true);
+        assert_eq!(DebugOutput(&state).to_string(), "_ +2 _ _ +5 9 ");
+        let (state, _) = step("1 +1 +2 +3", false);
+        assert_eq!(DebugOutput(&state).to_string(), "1 _ +1 3 ");
+
+        // Rules that don't crush it let it run into the stopped object instead
+        let merge = Merge { bounce: false };
+        let state: State = State::from_string("1 +5 +7 +9").unwrap();
+        let state = state.next(&merge).unwrap();
+        assert_eq!(DebugOutput(&state).to_string(), "1 _ +5 16 ");
+    }
+
+    #[test]
+    fn reports_events() {
+        let rule = Classic::WINNER_TAKES_ALL;
+        let state = State::from_string("+5 _ -3").unwrap();
+        let mut events: Vec<Event> = Vec::new();
+        let mut stats = Stats::default();
+        state.next_observed(&rule, 4, &mut (&mut events, &mut stats));
+        assert_eq!(
+            events,
+            [
+                Event {
+                    generation: 4,
+                    index: 0,
+                    moving: Moving::Right(5),
+                    destination: Cell::Empty,
+                    result: CollisionResult::MovingWon(Moving::Right(5)),
+                },
+                Event {
+                    generation: 4,
+                    index: 2,
+                    moving: Moving::Left(3),
+                    destination: Cell::InMotion(Moving::Right(5)),
+                    result: CollisionResult::DestinationWon(Cell::InMotion(Moving::Right(5))),
+                },
+            ]
+        );
+        assert_eq!(stats.collisions(), 1);
+        assert_eq!(stats.mass_destroyed, 3.0);
+
+        let mut indices = Vec::new();
+        state.next_observed(&rule, 1, &mut |event: &Event| indices.push(event.index));
+        assert_eq!(indices, [0, 2]);
+    }
+
+    #[test]
+    fn events_round_trip_through_json() {
+        let event = Event {
+            generation: 1,
+            index: 3,
+            moving: Moving::Left(7),
+            destination: Cell::InMotion(Moving::Left(2)),
+            result: CollisionResult::DestinationEscapes {
+                bumped: 2,
+                moved: Moving::Left(7),
+            },
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+    }
+
+    #[test]
+    fn accumulates_over_a_run() {
+        let rule = Classic::WINNER_TAKES_ALL;
+        let mut state: State = State::from_string("+9 _ _ -1 _ -2 _ 3").unwrap();
+        let mut total = Stats::of(&state);
+        assert_eq!(total.momentum_left, 3.0);
+        assert_eq!(total.momentum_right, 9.0);
+        while let Some((next, stats)) = state.next_with_stats(&rule) {
+            total.accumulate(&stats);
+            state = next;
+        }
+        assert_eq!(total.mass_destroyed, 6.0);
+        assert_eq!(total.mass_destroyed + state.mass(), 15.0);
+        assert_eq!(total.objects, 1);
+        assert_eq!(total.momentum_right, 9.0);
+
+        // Faster objects carry more momentum
+        let fast: State = State::from_string("+2@3 _ -4@1/2 5@2").unwrap();
+        assert_eq!(Stats::of(&fast).momentum_right, 6.0);
+        assert_eq!(Stats::of(&fast).momentum_left, 2.0);
+    }
+
+    #[test]
+    fn parses_speeds() {
+        let raw = "+3@12 _ -5@1/2 7@2 +1 ";
+        let state: State = State::from_string(raw).unwrap();
+        assert_eq!(
+            state.speed(0),
+            Speed {
+                cells: 12,
+                generations: 1
+            }
+        );
+        assert_eq!(
+            state.speed(2),
+            Speed {
+                cells: 1,
+                generations: 2
+            }
+        );
+        assert_eq!(state.speed(4), Speed::default());
+        assert_eq!(DebugOutput(&state).to_string(), raw);
+
+        for bad in ["+3@", "+3@0", "@3", "+3@1/0", "+3@2@2", "+3/2", "+3@2_"] {
+            assert!(State::<u32>::from_string(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn fast_objects_do_not_tunnel() {
+        let rule = Classic::WINNER_TAKES_ALL;
+        let run = |raw: &str| {
+            let mut state: State = State::from_string(raw).unwrap();
+            let mut rows = Vec::new();
+            while let Some(next) = state.next(&rule) {
+                state = next;
+                rows.push(DebugOutput(&state).to_string());
+            }
+            rows
+        };
+        // Without sub-steps these would jump straight past each other
+        assert_eq!(run("+5@3 _ _ _ -3@3 _ _"), ["_ _ _ +5@3 _ _ _ "]);
+        // Or past something slow that isn't due to move yet
+        assert_eq!(run("+9@4 _ _ -2@1/2 _ _ _"), ["_ _ _ _ +9@4 _ _ "]);
+        // Slow objects wait their turn
+        assert_eq!(
+            run("+2 _ _ _ -4@1/2 _"),
+            [
+                "_ +2 _ _ -4@1/2 _ ",
+                "_ _ +2 -4@1/2 _ _ ",
+                "_ _ _ -4@1/2 _ _ "
+            ]
+        );
+    }
+
+    fn ring(raw: &str) -> State {
+        State::from_string(raw)
+            .unwrap()
+            .with_topology(Topology::Ring)
+    }
+
+    #[test]
+    fn rings_wrap_around() {
+        let rule = Classic::WINNER_TAKES_ALL;
+        let next = |state: &State| DebugOutput(&state.next(&rule).unwrap()).to_string();
+        assert_eq!(next(&ring("-1 _ 4 _ _ -3")), "_ _ 4 _ -3 -1 ");
+        assert_eq!(next(&ring("_ 4 _ +3")), "+3 4 _ _ ");
+        // Meeting across the ends
+        assert_eq!(next(&ring("-2 _ _ +5")), "+5 _ _ _ ");
+        // A chain of bumps going round the end
+        let mut events: Vec<Event> = Vec::new();
+        let state = ring("+2 _ _ -1 _ +9 +4");
+        let after = state.next_observed(&rule, 1, &mut events).unwrap();
+        assert_eq!(DebugOutput(&after).to_string(), "+4 +2 -1 _ _ _ +9 ");
+        let bumps: Vec<usize> = events
+            .iter()
+            .filter(|e| matches!(e.result, CollisionResult::DestinationEscapes { .. }))
+            .map(|e| e.index)
+            .collect();
+        assert_eq!(bumps, [5, 6]);
+        // Nothing left to run into
+        assert!(ring("+2 _ +3 _ 4").next(&rule).is_some());
+        assert!(ring("+2 _ +3 _ +4").next(&rule).is_none());
+    }
+
+    /**
+     * Panics if anything ever gets to the end of a ring
+     */
+    struct NoWalls<R>(R);
+
+    impl<R: CollisionRule> CollisionRule for NoWalls<R> {
+        fn collide(&self, moving: &Moving, destination: &Cell) -> CollisionResult {
+            self.0.collide(moving, destination)
+        }
+
+        fn hit_wall(&self, moving: &Moving) -> Cell {
+            panic!("{moving:?} hit a wall")
+        }
+    }
+
+    #[test]
+    fn rings_have_no_walls() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        let rules: Vec<Box<dyn CollisionRule>> = vec![
+            Box::new(NoWalls(Classic {
+                bounce: false,
+                partial_destroy: true,
+            })),
+            Box::new(NoWalls(Elastic)),
+            Box::new(NoWalls(Merge { bounce: false })),
+        ];
+        let mut rng = StdRng::seed_from_u64(0x41e6);
+        for _ in 0..200 {
+            let len = rng.gen_range(1..30);
+            let state: State = State::random(len, 20, 30, 30, 20, &mut rng);
+            // Give some of them other speeds
+            let raw: Vec<String> = DebugOutput(&state)
+                .to_string()
+                .split_whitespace()
+                .map(|cell| match (cell, rng.gen_range(0..4)) {
+                    ("_", _) | (_, 0 | 1) => cell.to_string(),
+                    (_, 2) => format!("{cell}@3"),
+                    _ => format!("{cell}@1/2"),
+                })
+                .collect();
+            let state = ring(&raw.join(" "));
+            for rule in &rules {
+                let mut state = state.clone();
+                for _ in 0..50 {
+                    let Some(next) = state.next(rule.as_ref()) else {
+                        break;
+                    };
+                    assert!(next.mass() <= state.mass());
+                    state = next;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use thiserror::Error;
+
+/**
+ * What's wrong with a collider state string
+ */
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    #[error("Unexpected character: '{0}'")]
+    UnexpectedCharacter(char),
+    #[error("'{0}' must be at the start of a cell")]
+    MisplacedSign(char),
+    #[error("Only one prefix is allowed")]
+    TwoSigns,
+    #[error("Only one speed is allowed")]
+    TwoSpeeds,
+    #[error("'@' must come after a number")]
+    SpeedWithoutWeight,
+    #[error("'/' must be part of a speed")]
+    SlashOutsideSpeed,
+    #[error("'.' must be part of a weight")]
+    DotOutsideWeight,
+    #[error("'_' must be separated from other cells by a space")]
+    UnseparatedEmpty,
+    #[error("Invalid number ({raw}): {reason}")]
+    InvalidWeight { raw: String, reason: String },
+    #[error("Value must be between 0 and {max}")]
+    WeightOutOfRange { max: String },
+    #[error("{0}")]
+    InvalidSpeed(String),
+    #[error("Rows must all be the same length, expected {expected} cells but found {found}")]
+    UnevenRow { expected: usize, found: usize },
+}
+
+/**
+ * A `ParseErrorKind`, and where it was
+ *
+ * `offset` is in bytes from the start of the whole string, pointing at the
+ * character that was the problem or, for things that are wrong with a whole
+ * cell like a bad weight, the start of the cell. `cell` is the index the cell
+ * would have had in the state.
+ */
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Cell {}: {kind}", .cell + 1)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub offset: usize,
+    pub cell: usize,
+}
+
+impl ParseError {
+    /**
+     * The error with the line of input it came from underneath, and a caret
+     * pointing at the problem
+     */
+    pub fn diagnostic(&self, raw: &str) -> String {
+        let Some(before) = raw.get(..self.offset) else {
+            return format!("{self}\n\n    {raw}");
+        };
+        // Grids can be more than one line, and only the one with the problem
+        // in is any use
+        let start = before.rfind('\n').map_or(0, |i| i + 1);
+        let end = raw[self.offset..]
+            .find('\n')
+            .map_or(raw.len(), |i| self.offset + i);
+        // Count characters rather than bytes so the caret lines up
+        let column = raw[start..self.offset].chars().count();
+        format!(
+            "{self}\n\n    {}\n    {}^",
+            &raw[start..end],
+            " ".repeat(column)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::State;
+    use super::*;
+
+    fn error(raw: &str) -> ParseError {
+        State::<u32>::from_string(raw).unwrap_err()
+    }
+
+    #[test]
+    fn points_at_the_problem() {
+        assert_eq!(
+            error("+3 _ x"),
+            ParseError {
+                kind: ParseErrorKind::UnexpectedCharacter('x'),
+                offset: 5,
+                cell: 2
+            }
+        );
+        assert_eq!(
+            error("  _ 3-"),
+            ParseError {
+                kind: ParseErrorKind::MisplacedSign('-'),
+                offset: 5,
+                cell: 1
+            }
+        );
+        // Problems with a whole cell point at the start of it
+        assert_eq!(
+            error("_ -+3 _"),
+            ParseError {
+                kind: ParseErrorKind::TwoSigns,
+                offset: 2,
+                cell: 1
+            }
+        );
+        assert_eq!(
+            error("_ 99999999999").kind,
+            ParseErrorKind::InvalidWeight {
+                raw: "99999999999".to_string(),
+                reason: "number too large to fit in target type".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn draws_a_caret() {
+        assert_eq!(
+            error("+3 _ x").diagnostic("+3 _ x"),
+            "Cell 3: Unexpected character: 'x'\n\n    +3 _ x\n         ^"
+        );
+        // Lined up by characters, not bytes
+        let raw = "\u{3000}_ y";
+        assert_eq!(error(raw).offset, 5);
+        assert_eq!(
+            error(raw).diagnostic(raw),
+            "Cell 2: Unexpected character: 'y'\n\n    \u{3000}_ y\n       ^"
+        );
+        // Only the line with the problem in it, measured from its start
+        let raw = "+1 _ 2\n_ x _";
+        let error = super::super::grid::State::<u32>::from_string(raw).unwrap_err();
+        assert_eq!(
+            error.diagnostic(raw),
+            "Cell 5: Unexpected character: 'x'\n\n    _ x _\n      ^"
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use super::{Cell, Moving, Speed, State, Topology, Weight};
+
+/**
+ * Which way an object is going, if it's going anywhere
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Heading {
+    Left,
+    Right,
+    Still,
+}
+
+/**
+ * An object in a field being fast-forwarded, along with its neighbours
+ *
+ * Without bouncing or damage nothing ever changes speed or direction, it just
+ * stops at a wall, so where an object started is enough to know where it is in
+ * any generation.
+ */
+struct Object<W> {
+    heading: Heading,
+    weight: W,
+    /**
+     * Where it was in generation 0, or for anything stationary where it is
+     */
+    start: usize,
+    previous: Option<usize>,
+    next: Option<usize>,
+    alive: bool,
+}
+
+/**
+ * Something that happens to objects in a particular generation
+ */
+#[derive(Debug, Clone, Copy)]
+enum Event {
+    /**
+     * Neighbours run into each other, left one first
+     */
+    Collision(usize, usize),
+    /**
+     * The outermost object reaches the wall it's heading for and stops there
+     */
+    Wall(usize),
+}
+
+struct FastForward<W> {
+    len: usize,
+    objects: Vec<Object<W>>,
+    /**
+     * Events by generation, split into ones where the object doing the moving
+     * is going right and ones where it's going left. `next` goes through the
+     * field from the left, so when something is hit from both sides in the
+     * same generation it's the one going right that gets there first.
+     */
+    queue: Vec<[Vec<Event>; 2]>,
+    /**
+     * How many objects are going left, going right and standing still
+     */
+    counts: [usize; 3],
+}
+
+impl<W: Weight> FastForward<W> {
+    fn new(state: &State<W>) -> FastForward<W> {
+        let len = state.cells.len();
+        let mut objects: Vec<Object<W>> = Vec::new();
+        for (start, cell) in state.cells.iter().enumerate() {
+            let (heading, weight) = match cell {
+                Cell::Empty => continue,
+                Cell::Stationary(w) => (Heading::Still, *w),
+                Cell::InMotion(Moving::Left(w)) => (Heading::Left, *w),
+                Cell::InMotion(Moving::Right(w)) => (Heading::Right, *w),
+            };
+            let index = objects.len();
+            objects.push(Object {
+                heading,
+                weight,
+                start,
+                previous: index.checked_sub(1),
+                next: Some(index + 1),
+                alive: true,
+            });
+        }
+        if let Some(last) = objects.last_mut() {
+            last.next = None;
+        }
+
+        let mut counts = [0; 3];
+        for object in &objects {
+            counts[object.heading as usize] += 1;
+        }
+        // Nothing takes longer than crossing the whole field
+        let queue = (0..=len).map(|_| [Vec::new(), Vec::new()]).collect();
+        let mut fast_forward = FastForward {
+            len,
+            objects,
+            queue,
+            counts,
+        };
+        for index in 0..=fast_forward.objects.len() {
+            let left = index.checked_sub(1);
+            let right = (index < fast_forward.objects.len()).then_some(index);
+            fast_forward.neighbours(left, right);
+        }
+        fast_forward
+    }
+
+    /**
+     * The generation `event` happens in, if it's still going to
+     */
+    fn when(&self, event: Event) -> Option<usize> {
+        match event {
+            Event::Collision(a, b) => {
+                let (left, right) = (&self.objects[a], &self.objects[b]);
+                if !left.alive || !right.alive || left.next != Some(b) {
+                    return None;
+                }
+                match (left.heading, right.heading) {
+                    // Head on, they meet in the middle, or swap places if
+                    // there's no middle cell, which takes a generation longer
+                    (Heading::Right, Heading::Left) => Some((right.start - left.start).div_ceil(2)),
+                    (Heading::Right, Heading::Still) | (Heading::Still, Heading::Left) => {
+                        Some(right.start - left.start)
+                    }
+                    _ => None,
+                }
+            }
+            Event::Wall(a) => {
+                let a = &self.objects[a];
+                match a.heading {
+                    _ if !a.alive => None,
+                    Heading::Left if a.previous.is_none() => Some(a.start + 1),
+                    Heading::Right if a.next.is_none() => Some(self.len - a.start),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn schedule(&mut self, event: Event) {
+        let Some(generation) = self.when(event) else {
+            return;
+        };
+        let (Event::Collision(mover, _) | Event::Wall(mover)) = event;
+        let side = match self.objects[mover].heading {
+            Heading::Right => 0,
+            _ => 1,
+        };
+        self.queue[generation][side].push(event);
+    }
+
+    /**
+     * Works out what's going to happen between two objects that have just
+     * ended up next to each other, where `None` is a wall
+     */
+    fn neighbours(&mut self, left: Option<usize>, right: Option<usize>) {
+        match (left, right) {
+            (Some(a), Some(b)) => self.schedule(Event::Collision(a, b)),
+            (Some(a), None) | (None, Some(a)) => self.schedule(Event::Wall(a)),
+            (None, None) => {}
+        }
+    }
+
+    fn remove(&mut self, index: usize) {
+        let Object { previous, next, .. } = self.objects[index];
+        if let Some(previous) = previous {
+            self.objects[previous].next = next;
+        }
+        if let Some(next) = next {
+            self.objects[next].previous = previous;
+        }
+        self.objects[index].alive = false;
+        self.counts[self.objects[index].heading as usize] -= 1;
+    }
+
+    /**
+     * Carries out `event` if it really does happen in `generation`, since
+     * plenty of them are called off by something else happening first
+     */
+    fn happen(&mut self, event: Event, generation: usize) -> bool {
+        if self.when(event) != Some(generation) {
+            return false;
+        }
+        match event {
+            Event::Collision(a, b) => {
+                let (weight_a, weight_b) = (self.objects[a].weight, self.objects[b].weight);
+                if weight_a >= weight_b {
+                    self.remove(b);
+                }
+                if weight_a <= weight_b {
+                    self.remove(a);
+                }
+                let left = match self.objects[a].alive {
+                    true => Some(a),
+                    false => self.objects[a].previous,
+                };
+                let right = match self.objects[b].alive {
+                    true => Some(b),
+                    false => self.objects[b].next,
+                };
+                self.neighbours(left, right);
+            }
+            Event::Wall(a) => {
+                // Anything going right right behind it bumps it into the
+                // wall and gets crushed. Going left, the one at the wall
+                // moves first, so it's already stopped when the next one
+                // gets there.
+                let object = &self.objects[a];
+                if let Some(previous) = object.previous
+                    && object.heading == Heading::Right
+                    && self.objects[previous].heading == Heading::Right
+                    && self.objects[previous].start + 1 == object.start
+                {
+                    self.remove(previous);
+                }
+                let object = &mut self.objects[a];
+                let (start, left, right) = match object.heading {
+                    Heading::Left => (0, Some(a), object.next),
+                    _ => (self.len - 1, object.previous, Some(a)),
+                };
+                self.counts[object.heading as usize] -= 1;
+                self.counts[Heading::Still as usize] += 1;
+                object.heading = Heading::Still;
+                object.start = start;
+                self.neighbours(left, right);
+            }
+        }
+        true
+    }
+
+    /**
+     * The same check `next` does before moving anything, that everything left
+     * is of the same kind
+     */
+    fn finished(&self) -> bool {
+        self.counts.iter().filter(|&&count| count > 0).count() <= 1
+    }
+
+    fn state(&self, generation: usize) -> State<W> {
+        let mut cells = vec![Cell::Empty; self.len];
+        for object in self.objects.iter().filter(|o| o.alive) {
+            let w = object.weight;
+            let (index, cell) = match object.heading {
+                Heading::Left => (object.start - generation, Cell::InMotion(Moving::Left(w))),
+                Heading::Right => (object.start + generation, Cell::InMotion(Moving::Right(w))),
+                Heading::Still => (object.start, Cell::Stationary(w)),
+            };
+            cells[index] = cell;
+        }
+        State::new(cells)
+    }
+}
+
+impl<W: Weight> State<W> {
+    /**
+     * Skips straight to the end of a run with `Classic` physics that doesn't
+     * bounce or take damage, giving the state `next` would stop at and how
+     * many generations it would take to get there
+     *
+     * Rather than moving every object every generation, this jumps from one
+     * collision to the next. Objects only ever run into their neighbours, and
+     * when one is destroyed its neighbours become each other's, so there are
+     * never more collisions to look at than there were objects. It's O(n) in
+     * the length of the field, however long the run would have been.
+     *
+     * This is the "asteroid collision" puzzle the collider started out as,
+     * except stationary objects can be hit from both sides, so what happens
+     * when weights tie depends on which side gets there first, and that needs
+     * the timings. Only works with walls and everything going one cell a
+     * generation.
+     */
+    pub fn fast_forward(&self) -> Result<(State<W>, usize), String> {
+        if self.topology != Topology::Walls {
+            return Err("Fast-forwarding only works with walls".to_string());
+        }
+        if self.motion.iter().any(|m| m.speed != Speed::default()) {
+            return Err("Fast-forwarding only works at one cell a generation".to_string());
+        }
+        let mut fast_forward = FastForward::new(self);
+        if fast_forward.finished() {
+            return Ok((self.clone(), 0));
+        }
+        for generation in 1..fast_forward.queue.len() {
+            let mut changed = false;
+            loop {
+                let [right, left] = &mut fast_forward.queue[generation];
+                let Some(event) = right.pop().or_else(|| left.pop()) else {
+                    break;
+                };
+                changed |= fast_forward.happen(event, generation);
+            }
+            if changed && fast_forward.finished() {
+                return Ok((fast_forward.state(generation), generation));
+            }
+        }
+        unreachable!("everything ends up stopped at a wall or destroyed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::super::{Classic, DebugOutput};
+    use super::*;
+
+    fn check<W: Weight>(state: &State<W>) {
+        let (expected, generations, _) = state.clone().settle(&Classic::WINNER_TAKES_ALL, 0);
+        let (actual, fast_generations) = state.fast_forward().unwrap();
+        assert_eq!(
+            (DebugOutput(&actual).to_string(), fast_generations as u64),
+            (DebugOutput(&expected).to_string(), generations),
+            "from {}",
+            DebugOutput(state)
+        );
+    }
+
+    #[test]
+    fn agrees_with_next() {
+        let mut rng = StdRng::seed_from_u64(0xa57e);
+        for _ in 0..5000 {
+            let length = rng.gen_range(0..40);
+            let mut chances = [0; 4];
+            chances.iter_mut().for_each(|c| *c = rng.gen_range(0..10));
+            // Weights from a small range so there are plenty of ties
+            let heaviest = rng.gen_range(1..6);
+            let cells: Vec<Cell<u32>> = (0..length)
+                .map(|_| {
+                    let w = rng.gen_range(1..=heaviest);
+                    match rng.gen_range(0..=chances.iter().sum::<u32>()) {
+                        c if c < chances[0] => Cell::Stationary(w),
+                        c if c < chances[0] + chances[1] => Cell::InMotion(Moving::Left(w)),
+                        c if c < chances[0] + chances[1] + chances[2] => {
+                            Cell::InMotion(Moving::Right(w))
+                        }
+                        _ => Cell::Empty,
+                    }
+                })
+                .collect();
+            check(&State::new(cells));
+        }
+        for _ in 0..200 {
+            check::<u32>(&State::random(200, 20, 20, 20, 40, &mut rng));
+        }
+    }
+
+    #[test]
+    fn stops_where_next_does() {
+        let state: State = State::from_string("+3 _ 3 _ -3").unwrap();
+        let (end, generations) = state.fast_forward().unwrap();
+        assert_eq!(DebugOutput(&end).to_string(), "_ _ -3 _ _ ");
+        assert_eq!(generations, 2);
+
+        // Already finished
+        let state: State = State::from_string("-1 _ -2").unwrap();
+        assert_eq!(state.fast_forward().unwrap().1, 0);
+
+        let state: State = State::from_string("+1@2 -1").unwrap();
+        assert!(state.fast_forward().is_err());
+        let state: State = State::from_string("+1 -1").unwrap();
+        assert!(state.with_topology(Topology::Ring).fast_forward().is_err());
+    }
+}
@@ -0,0 +1,474 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use super::{Cell, Motion, Moving, State, Weight};
+
+/**
+ * How the weights of objects in a random state are drawn
+ *
+ * Written as a name and its parameters, like `uniform:1,99`, `normal:50,10`
+ * or `power-law:2.5`. Draws are never lighter than 1, so everything generated
+ * has some weight to it.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Distribution {
+    /**
+     * Whole numbers from 1 to 99, drawn exactly like `Weight::random`
+     */
+    #[default]
+    Standard,
+    /**
+     * Whole numbers from `low` to `high`, including both
+     */
+    Uniform {
+        low: u32,
+        high: u32,
+    },
+    Normal {
+        mean: f64,
+        deviation: f64,
+    },
+    /**
+     * Mostly light objects, with the odd very heavy one. The chance of a
+     * weight `w` goes as `w` to the minus `exponent`, which has to be more
+     * than 1.
+     */
+    PowerLaw {
+        exponent: f64,
+    },
+}
+
+impl Distribution {
+    pub fn sample<W: Weight, R: Rng + ?Sized>(&self, r: &mut R) -> W {
+        let value = match *self {
+            Distribution::Standard => return W::random(r),
+            Distribution::Uniform { low, high } => r.gen_range(low..=high).into(),
+            Distribution::Normal { mean, deviation } => {
+                // Box-Muller, `1 - u` so there's no log of 0
+                let u: f64 = 1.0 - r.r#gen::<f64>();
+                let v: f64 = r.r#gen();
+                let z = (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+                mean + deviation * z
+            }
+            Distribution::PowerLaw { exponent } => {
+                // Inverting the Pareto distribution's CDF, starting from 1
+                let u: f64 = 1.0 - r.r#gen::<f64>();
+                u.powf(-1.0 / (exponent - 1.0))
+            }
+        };
+        W::from_f64(value.max(1.0))
+    }
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Distribution, String> {
+        let (name, parameters) = raw.split_once(':').unwrap_or((raw, ""));
+        let parameters: Vec<&str> = match parameters {
+            "" => Vec::new(),
+            p => p.split(',').map(str::trim).collect(),
+        };
+        let number = |i: usize| -> Result<f64, String> {
+            let p = parameters[i];
+            p.parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| format!("Expected a number, got '{p}'"))
+        };
+        let expect = |count: usize, form: &str| match parameters.len() == count {
+            true => Ok(()),
+            false => Err(format!("Expected '{form}', got '{raw}'")),
+        };
+        match name.trim() {
+            "standard" => {
+                expect(0, "standard")?;
+                Ok(Distribution::Standard)
+            }
+            "uniform" => {
+                expect(2, "uniform:LOW,HIGH")?;
+                let bound = |i: usize| {
+                    parameters[i]
+                        .parse::<u32>()
+                        .map_err(|e| format!("Invalid bound '{}': {e}", parameters[i]))
+                };
+                let (low, high) = (bound(0)?, bound(1)?);
+                if low > high {
+                    return Err(format!("The low end, {low}, is above the high end, {high}"));
+                }
+                Ok(Distribution::Uniform { low, high })
+            }
+            "normal" => {
+                expect(2, "normal:MEAN,DEVIATION")?;
+                let deviation = number(1)?;
+                if deviation < 0.0 {
+                    return Err("The deviation can't be negative".to_string());
+                }
+                Ok(Distribution::Normal {
+                    mean: number(0)?,
+                    deviation,
+                })
+            }
+            "power-law" => {
+                expect(1, "power-law:EXPONENT")?;
+                let exponent = number(0)?;
+                if exponent <= 1.0 {
+                    return Err("The exponent has to be more than 1".to_string());
+                }
+                Ok(Distribution::PowerLaw { exponent })
+            }
+            _ => Err(format!(
+                "Unknown distribution '{name}', expected standard, uniform, normal or power-law"
+            )),
+        }
+    }
+}
+
+impl Display for Distribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Distribution::Standard => write!(f, "standard"),
+            Distribution::Uniform { low, high } => write!(f, "uniform:{low},{high}"),
+            Distribution::Normal { mean, deviation } => write!(f, "normal:{mean},{deviation}"),
+            Distribution::PowerLaw { exponent } => write!(f, "power-law:{exponent}"),
+        }
+    }
+}
+
+/**
+ * Where the weights of each kind of object in a random state come from
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Weights {
+    pub stationary: Distribution,
+    pub left: Distribution,
+    pub right: Distribution,
+}
+
+/**
+ * Exactly how many of each kind of object to put in a random state, with the
+ * rest of it empty. Written as `STATIONARY,LEFT,RIGHT`.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub stationary: usize,
+    pub left: usize,
+    pub right: usize,
+}
+
+impl Counts {
+    /**
+     * How many objects there are altogether, or an error if that's too many
+     * to even count
+     */
+    pub fn total(&self) -> Result<usize, String> {
+        self.stationary
+            .checked_add(self.left)
+            .and_then(|n| n.checked_add(self.right))
+            .ok_or_else(|| {
+                format!(
+                    "Too many objects to count: {},{},{}",
+                    self.stationary, self.left, self.right
+                )
+            })
+    }
+}
+
+impl FromStr for Counts {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Counts, String> {
+        let counts = raw
+            .split(',')
+            .map(|c| {
+                c.trim()
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid count '{c}': {e}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match counts[..] {
+            [stationary, left, right] => Ok(Counts {
+                stationary,
+                left,
+                right,
+            }),
+            _ => Err(format!(
+                "Expected three counts, 'STATIONARY,LEFT,RIGHT', got '{raw}'"
+            )),
+        }
+    }
+}
+
+impl<W: Weight> State<W> {
+    /**
+     * Like `State::random`, but with the weights drawn from `weights`
+     */
+    pub fn random_with<R: Rng>(
+        length: usize,
+        weight_stationary: u16,
+        weight_left: u16,
+        weight_right: u16,
+        weight_empty: u16,
+        weights: &Weights,
+        r: &mut R,
+    ) -> State<W> {
+        // Weighted random alg stolen from https://stackoverflow.com/a/8435261
+        let total_weight = u32::from(weight_stationary)
+            + u32::from(weight_left)
+            + u32::from(weight_right)
+            + u32::from(weight_empty);
+        let spec_stationary = u32::from(weight_stationary);
+        let spec_left = spec_stationary + u32::from(weight_left);
+        let spec_right = spec_left + u32::from(weight_right);
+
+        let cells: Vec<Cell<W>> = (0..length)
+            .map(|_| {
+                // With no chance of anything, there's nothing
+                if total_weight == 0 {
+                    return Cell::Empty;
+                }
+                let choice = r.gen_range(0..total_weight);
+                if choice < spec_stationary {
+                    Cell::Stationary(weights.stationary.sample(r))
+                } else if choice < spec_left {
+                    Cell::InMotion(Moving::Left(weights.left.sample(r)))
+                } else if choice < spec_right {
+                    Cell::InMotion(Moving::Right(weights.right.sample(r)))
+                } else {
+                    Cell::Empty
+                }
+            })
+            .collect();
+        State::new(cells)
+    }
+
+    /**
+     * Exactly `counts` objects, shuffled into a field of `length` cells
+     */
+    pub fn with_counts<R: Rng>(
+        length: usize,
+        counts: Counts,
+        weights: &Weights,
+        r: &mut R,
+    ) -> Result<State<W>, String> {
+        let total = counts.total()?;
+        if total > length {
+            return Err(format!("{total} objects don't fit in {length} cells"));
+        }
+        let mut cells: Vec<Cell<W>> = Vec::with_capacity(length);
+        cells
+            .extend((0..counts.stationary).map(|_| Cell::Stationary(weights.stationary.sample(r))));
+        cells
+            .extend((0..counts.left).map(|_| Cell::InMotion(Moving::Left(weights.left.sample(r)))));
+        cells.extend(
+            (0..counts.right).map(|_| Cell::InMotion(Moving::Right(weights.right.sample(r)))),
+        );
+        cells.resize(length, Cell::Empty);
+        cells.shuffle(r);
+        Ok(State::new(cells))
+    }
+
+    /**
+     * This state followed by its mirror image, with everything going the
+     * other way, and `gap` empty cells between them
+     *
+     * It's only symmetric to start with. `next` works through the field from
+     * the left, so when things on both sides depend on which order they're
+     * moved in, the two halves can end up different.
+     */
+    pub fn mirrored(&self, gap: usize) -> State<W> {
+        let flipped = self.cells.iter().rev().map(|cell| match cell {
+            Cell::InMotion(Moving::Left(w)) => Cell::InMotion(Moving::Right(*w)),
+            Cell::InMotion(Moving::Right(w)) => Cell::InMotion(Moving::Left(*w)),
+            cell => cell.clone(),
+        });
+        let cells = self
+            .cells
+            .iter()
+            .cloned()
+            .chain(std::iter::repeat_n(Cell::Empty, gap))
+            .chain(flipped)
+            .collect();
+        let motion = self
+            .motion
+            .iter()
+            .copied()
+            .chain(std::iter::repeat_n(Motion::default(), gap))
+            .chain(self.motion.iter().rev().copied())
+            .collect();
+        State {
+            cells,
+            motion,
+            topology: self.topology,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::super::DebugOutput;
+    use super::*;
+
+    fn objects(state: &State) -> (usize, usize, usize) {
+        let count = |f: fn(&Cell) -> bool| state.cells.iter().filter(|c| f(c)).count();
+        (
+            count(|c| matches!(c, Cell::Stationary(_))),
+            count(|c| matches!(c, Cell::InMotion(Moving::Left(_)))),
+            count(|c| matches!(c, Cell::InMotion(Moving::Right(_)))),
+        )
+    }
+
+    fn weights_of(state: &State) -> Vec<u32> {
+        state
+            .cells
+            .iter()
+            .filter_map(|c| match c {
+                Cell::Empty => None,
+                Cell::Stationary(w) | Cell::InMotion(Moving::Left(w) | Moving::Right(w)) => {
+                    Some(*w)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exact_counts() {
+        let mut rng = StdRng::seed_from_u64(24);
+        let counts = Counts {
+            stationary: 3,
+            left: 5,
+            right: 7,
+        };
+        for _ in 0..20 {
+            let state: State =
+                State::with_counts(40, counts, &Weights::default(), &mut rng).unwrap();
+            assert_eq!(state.cells.len(), 40);
+            assert_eq!(objects(&state), (3, 5, 7));
+        }
+        let full: State = State::with_counts(15, counts, &Weights::default(), &mut rng).unwrap();
+        assert_eq!(objects(&full), (3, 5, 7));
+        assert!(State::<u32>::with_counts(14, counts, &Weights::default(), &mut rng).is_err());
+        // Too many to add up shouldn't panic either
+        let huge: Counts = format!("{},1,1", usize::MAX).parse().unwrap();
+        assert!(huge.total().is_err());
+        assert!(State::<u32>::with_counts(14, huge, &Weights::default(), &mut rng).is_err());
+    }
+
+    #[test]
+    fn no_chance_of_empty_means_no_empties() {
+        let mut rng = StdRng::seed_from_u64(24);
+        let state: State = State::random(500, 1, 0, 0, 0, &mut rng);
+        assert_eq!(objects(&state), (500, 0, 0));
+        let state: State = State::random(500, 0, 0, 0, 0, &mut rng);
+        assert_eq!(objects(&state), (0, 0, 0));
+    }
+
+    #[test]
+    fn draws_from_distributions() {
+        let mut rng = StdRng::seed_from_u64(24);
+        let draw = |distribution: &str, rng: &mut StdRng| -> Vec<u32> {
+            let distribution: Distribution = distribution.parse().unwrap();
+            let weights = Weights {
+                stationary: distribution,
+                left: distribution,
+                right: distribution,
+            };
+            let state = State::random_with(2000, 1, 1, 1, 0, &weights, rng);
+            weights_of(&state)
+        };
+
+        let uniform = draw("uniform:500,510", &mut rng);
+        assert!(uniform.iter().all(|w| (500..=510).contains(w)));
+        let mean = |ws: &[u32]| ws.iter().map(|&w| f64::from(w)).sum::<f64>() / ws.len() as f64;
+        let normal = draw("normal:1000,10", &mut rng);
+        assert!((mean(&normal) - 1000.0).abs() < 2.0, "{}", mean(&normal));
+        // Never lighter than 1, however far down the distribution goes
+        assert!(draw("normal:0,5", &mut rng).iter().all(|&w| w >= 1));
+        let power = draw("power-law:2", &mut rng);
+        assert!(power.iter().all(|&w| w >= 1));
+        let ones = power.iter().filter(|&&w| w == 1).count();
+        // A power law with exponent 2 is below 1.5, so rounds to 1, a third
+        // of the time
+        assert!((550..800).contains(&ones), "{ones}");
+        assert!(power.iter().any(|&w| w > 100));
+    }
+
+    #[test]
+    fn standard_weights() {
+        // Pinned so that changes to how random states are drawn, which
+        // change what every seed gives, don't go unnoticed
+        let state = |weights: &Weights| {
+            let state: State =
+                State::random_with(12, 20, 20, 20, 40, weights, &mut StdRng::seed_from_u64(7));
+            DebugOutput(&state).to_string()
+        };
+        assert_eq!(
+            state(&Weights::default()),
+            "+4 31 15 _ +62 -73 _ +18 _ -47 38 _ "
+        );
+        let uniform = Weights {
+            right: Distribution::Uniform { low: 1, high: 1 },
+            ..Weights::default()
+        };
+        assert_eq!(state(&uniform), "+1 31 15 _ +1 _ _ +1 _ -47 38 _ ");
+    }
+
+    #[test]
+    fn mirror_images() {
+        let state: State = State::from_string("+3 _ 5 -2").unwrap();
+        assert_eq!(
+            DebugOutput(&state.mirrored(1)).to_string(),
+            "+3 _ 5 -2 _ +2 5 _ -3 "
+        );
+        assert_eq!(
+            DebugOutput(&state.mirrored(0)).to_string(),
+            "+3 _ 5 -2 +2 5 _ -3 "
+        );
+        // Speeds come along too
+        let state: State = State::from_string("+3@2 _ 5 -2@1/2").unwrap();
+        assert_eq!(
+            DebugOutput(&state.mirrored(1)).to_string(),
+            "+3@2 _ 5 -2@1/2 _ +2@1/2 5 _ -3@2 "
+        );
+    }
+
+    #[test]
+    fn parses_distributions() {
+        for raw in [
+            "standard",
+            "uniform:1,99",
+            "normal:50,12.5",
+            "power-law:2.5",
+        ] {
+            assert_eq!(raw.parse::<Distribution>().unwrap().to_string(), raw);
+        }
+        for raw in [
+            "uniform:9,1",
+            "uniform:1",
+            "normal:50",
+            "normal:50,-1",
+            "power-law:1",
+            "power-law:x",
+            "gaussian:1,2",
+            "standard:1",
+        ] {
+            assert!(raw.parse::<Distribution>().is_err(), "{raw}");
+        }
+        assert_eq!(
+            "1, 2,3".parse(),
+            Ok(Counts {
+                stationary: 1,
+                left: 2,
+                right: 3
+            })
+        );
+        assert!("1,2".parse::<Counts>().is_err());
+        assert!("1,2,x".parse::<Counts>().is_err());
+    }
+}
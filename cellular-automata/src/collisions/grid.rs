@@ -0,0 +1,626 @@
+use std::fmt::{Display, Write};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{CollisionResult, CollisionRule, Moving, ParseError, ParseErrorKind, Weight};
+
+/**
+ * Which way an object on the grid is going
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    pub fn reversed(self) -> Direction {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    /**
+     * Whether this is across `other` rather than along it
+     */
+    fn crosses(self, other: Direction) -> bool {
+        other != self && other != self.reversed()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cell<W = u32> {
+    Empty,
+    Stationary(W),
+    InMotion(Direction, W),
+}
+
+/**
+ * What `destination` looks like to something going `heading`, turned round so
+ * that it's going right along a row, which is what the rules understand
+ *
+ * Anything going across `heading` isn't moving towards or away from it, so it
+ * looks stationary.
+ */
+fn relative<W: Weight>(heading: Direction, destination: &Cell<W>) -> super::Cell<W> {
+    match destination {
+        Cell::Empty => super::Cell::Empty,
+        Cell::Stationary(w) => super::Cell::Stationary(*w),
+        Cell::InMotion(d, w) if *d == heading => super::Cell::InMotion(Moving::Right(*w)),
+        Cell::InMotion(d, w) if *d == heading.reversed() => super::Cell::InMotion(Moving::Left(*w)),
+        Cell::InMotion(_, w) => super::Cell::Stationary(*w),
+    }
+}
+
+/**
+ * Turns a cell from a rule back the right way round. `sideways` is the way
+ * the cell was going across `heading`, if it was, which it carries on doing
+ * if the rule leaves it stationary.
+ */
+fn absolute<W: Weight>(
+    heading: Direction,
+    cell: super::Cell<W>,
+    sideways: Option<Direction>,
+) -> Cell<W> {
+    match cell {
+        super::Cell::Empty => Cell::Empty,
+        super::Cell::Stationary(w) => match sideways {
+            Some(direction) => Cell::InMotion(direction, w),
+            None => Cell::Stationary(w),
+        },
+        super::Cell::InMotion(Moving::Right(w)) => Cell::InMotion(heading, w),
+        super::Cell::InMotion(Moving::Left(w)) => Cell::InMotion(heading.reversed(), w),
+    }
+}
+
+/**
+ * A two-dimensional collider, where objects go up, down, left or right
+ *
+ * This uses the same `CollisionRule`s as the one-dimensional collider. Every
+ * collision happens along the direction the moving object is going, so
+ * head-on collisions and running into the back of something are just like
+ * they are on a row. Hitting something going across its path from the side,
+ * the moving object sees it as stationary, and if it comes out of the
+ * collision stationary it keeps going the way it was. Anything the rule sets
+ * moving goes along the direction of the collision though, it can't go both
+ * ways at once.
+ *
+ * The grid has walls all round, and everything goes one cell a generation.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State<W = u32> {
+    width: usize,
+    height: usize,
+    /**
+     * Row by row, from the top
+     */
+    cells: Vec<Cell<W>>,
+}
+
+impl<W: Weight> State<W> {
+    /**
+     * A random grid, with the chance of each cell being stationary, moving or
+     * empty given by the weights. Moving objects are equally likely to be
+     * going any of the four ways.
+     */
+    pub fn random<R: Rng>(
+        width: usize,
+        height: usize,
+        weight_stationary: u16,
+        weight_moving: u16,
+        weight_empty: u16,
+        r: &mut R,
+    ) -> State<W> {
+        let total_weight =
+            u32::from(weight_stationary) + u32::from(weight_moving) + u32::from(weight_empty);
+        let cells = (0..width * height)
+            .map(|_| {
+                if total_weight == 0 {
+                    return Cell::Empty;
+                }
+                let choice = r.gen_range(0..total_weight);
+                if choice < weight_stationary.into() {
+                    Cell::Stationary(W::random(r))
+                } else if choice < u32::from(weight_stationary) + u32::from(weight_moving) {
+                    let direction = Direction::ALL[r.gen_range(0..4)];
+                    Cell::InMotion(direction, W::random(r))
+                } else {
+                    Cell::Empty
+                }
+            })
+            .collect();
+        State {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /**
+     * Rows of cells like the one-dimensional collider's, with `^` and `v` for
+     * objects going up and down. Rows are separated by newlines or `;`, and
+     * all have to be the same length. There aren't any speeds.
+     */
+    pub fn from_string(raw: &str) -> Result<State<W>, ParseError> {
+        let mut cells = Vec::new();
+        let mut width = None;
+        let mut height = 0;
+        let mut row_start = 0;
+        for row in raw.split(['\n', ';']) {
+            let start = row_start;
+            row_start += row.len() + 1;
+            let mut row_width = 0;
+            for (offset, token) in tokens(row) {
+                let offset = start + offset;
+                let cell = parse_cell(token).map_err(|(kind, within)| ParseError {
+                    kind,
+                    offset: offset + within,
+                    cell: cells.len(),
+                })?;
+                cells.push(cell);
+                row_width += 1;
+            }
+            if row_width == 0 {
+                continue;
+            }
+            match width {
+                None => width = Some(row_width),
+                Some(expected) if expected != row_width => {
+                    return Err(ParseError {
+                        kind: ParseErrorKind::UnevenRow {
+                            expected,
+                            found: row_width,
+                        },
+                        offset: start + (row.len() - row.trim_start().len()),
+                        cell: cells.len() - row_width,
+                    });
+                }
+                Some(_) => {}
+            }
+            height += 1;
+        }
+        Ok(State {
+            width: width.unwrap_or(0),
+            height,
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> &Cell<W> {
+        &self.cells[y * self.width + x]
+    }
+
+    /**
+     * The cell next to `index` going `direction`, unless that's a wall
+     */
+    fn neighbour(&self, index: usize, direction: Direction) -> Option<usize> {
+        let (x, y) = (index % self.width, index / self.width);
+        match direction {
+            Direction::Up if y > 0 => Some(index - self.width),
+            Direction::Down if y + 1 < self.height => Some(index + self.width),
+            Direction::Left if x > 0 => Some(index - 1),
+            Direction::Right if x + 1 < self.width => Some(index + 1),
+            _ => None,
+        }
+    }
+
+    /**
+     * Once everything is stationary, or going the same way, nothing else can
+     * ever collide
+     */
+    fn settled(&self) -> bool {
+        let mut kinds = self.cells.iter().filter_map(|cell| match cell {
+            Cell::Empty => None,
+            Cell::Stationary(_) => Some(None),
+            Cell::InMotion(direction, _) => Some(Some(*direction)),
+        });
+        match kinds.next() {
+            Some(first) => kinds.all(|kind| kind == first),
+            None => true,
+        }
+    }
+
+    /**
+     * Moves the object in `index` a cell, which can mean shoving whatever's in
+     * front of it along first, like `State::move_left` does
+     */
+    fn move_object(&mut self, index: usize, moved: &mut [bool], rule: &dyn CollisionRule<W>) {
+        let Cell::InMotion(heading, weight) = self.cells[index] else {
+            return;
+        };
+        moved[index] = true;
+        let Some(target) = self.neighbour(index, heading) else {
+            self.cells[index] = absolute(heading, rule.hit_wall(&Moving::Right(weight)), None);
+            return;
+        };
+        let destination = self.cells[target].clone();
+        if moved[target] && matches!(destination, Cell::InMotion(d, _) if d == heading) {
+            // It's already had its turn so can't be shoved along again, and
+            // it's going the same way so there's nothing to hit
+            return;
+        }
+        let sideways = match destination {
+            Cell::InMotion(direction, _) if heading.crosses(direction) => Some(direction),
+            _ => None,
+        };
+        match rule.collide(&Moving::Right(weight), &relative(heading, &destination)) {
+            CollisionResult::BothDestroyed => {
+                self.cells[index] = Cell::Empty;
+                self.cells[target] = Cell::Empty;
+            }
+            CollisionResult::MovingWon(moving) => {
+                self.cells[index] = Cell::Empty;
+                self.cells[target] = absolute(heading, super::Cell::InMotion(moving), None);
+                moved[target] = true;
+            }
+            CollisionResult::DestinationWon(cell) => {
+                self.cells[index] = Cell::Empty;
+                self.cells[target] = absolute(heading, cell, sideways);
+            }
+            CollisionResult::Rebound {
+                moving,
+                destination,
+            } => {
+                self.cells[index] = absolute(heading, moving, None);
+                self.cells[target] = absolute(heading, destination, sideways);
+                // Being hit was its turn
+                moved[target] = true;
+            }
+            CollisionResult::DestinationEscapes { bumped, moved: m } => {
+                self.cells[index] = absolute(heading, super::Cell::InMotion(m), None);
+                self.cells[target] = Cell::InMotion(heading, bumped);
+                self.move_object(target, moved, rule);
+                self.move_object(index, moved, rule);
+            }
+        }
+    }
+
+    /**
+     * Moves everything a cell, or gives `None` once nothing else can collide
+     *
+     * Objects move one at a time, row by row from the top left, so like on a
+     * row, two objects heading for the same cell collide there, and whichever
+     * gets there first is the one hit.
+     */
+    pub fn next(&self, rule: &dyn CollisionRule<W>) -> Option<State<W>> {
+        if self.settled() {
+            return None;
+        }
+        let mut next = self.clone();
+        let mut moved = vec![false; self.cells.len()];
+        for index in 0..next.cells.len() {
+            if !moved[index] {
+                next.move_object(index, &mut moved, rule);
+            }
+        }
+        Some(next)
+    }
+
+    /**
+     * Total weight of everything on the grid
+     */
+    pub fn mass(&self) -> f64 {
+        self.cells
+            .iter()
+            .map(|cell| match cell {
+                Cell::Empty => 0.0,
+                Cell::Stationary(w) | Cell::InMotion(_, w) => w.to_f64(),
+            })
+            .sum()
+    }
+}
+
+/**
+ * The whitespace separated tokens in `row`, with their offsets
+ */
+fn tokens(row: &str) -> impl Iterator<Item = (usize, &str)> {
+    row.split(' ')
+        .scan(0, |offset, token| {
+            let start = *offset;
+            *offset += token.len() + 1;
+            Some((start, token))
+        })
+        .map(|(offset, token)| {
+            let trimmed = token.trim_start();
+            (offset + token.len() - trimmed.len(), trimmed.trim_end())
+        })
+        .filter(|(_, token)| !token.is_empty())
+}
+
+/**
+ * A single cell, or what was wrong with it and where in the token
+ */
+fn parse_cell<W: Weight>(token: &str) -> Result<Cell<W>, (ParseErrorKind, usize)> {
+    if token == "_" {
+        return Ok(Cell::Empty);
+    }
+    let direction = match token.chars().next() {
+        Some('^') => Some(Direction::Up),
+        Some('v') => Some(Direction::Down),
+        Some('-') => Some(Direction::Left),
+        Some('+') => Some(Direction::Right),
+        _ => None,
+    };
+    let start = if direction.is_some() { 1 } else { 0 };
+    let weight = &token[start..];
+    for (offset, c) in weight.char_indices() {
+        let kind = match c {
+            _ if c.is_ascii_digit() || c == '.' => continue,
+            '^' | 'v' | '-' | '+' if offset == 0 => ParseErrorKind::TwoSigns,
+            '^' | 'v' | '-' | '+' => ParseErrorKind::MisplacedSign(c),
+            '_' => ParseErrorKind::UnseparatedEmpty,
+            _ => ParseErrorKind::UnexpectedCharacter(c),
+        };
+        let within = match kind {
+            ParseErrorKind::TwoSigns => 0,
+            _ => start + offset,
+        };
+        return Err((kind, within));
+    }
+    let value = weight.parse::<W>().map_err(|e| {
+        let kind = ParseErrorKind::InvalidWeight {
+            raw: weight.to_string(),
+            reason: e.to_string(),
+        };
+        (kind, 0)
+    })?;
+    if !value.is_valid() {
+        let max = W::MAX.to_string();
+        return Err((ParseErrorKind::WeightOutOfRange { max }, 0));
+    }
+    Ok(match direction {
+        Some(direction) => Cell::InMotion(direction, value),
+        None => Cell::Stationary(value),
+    })
+}
+
+/**
+ * A row of characters for each row of the grid, like the one-dimensional
+ * collider, with `o` for stationary objects since `^` means going up
+ */
+impl<W: Weight> Display for State<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (y, row) in self.cells.chunks(self.width.max(1)).enumerate() {
+            if y > 0 {
+                f.write_char('\n')?;
+            }
+            f.write_char('|')?;
+            for cell in row {
+                f.write_char(match cell {
+                    Cell::Empty => '-',
+                    Cell::Stationary(_) => 'o',
+                    Cell::InMotion(Direction::Up, _) => '^',
+                    Cell::InMotion(Direction::Down, _) => 'v',
+                    Cell::InMotion(Direction::Left, _) => '<',
+                    Cell::InMotion(Direction::Right, _) => '>',
+                })?;
+            }
+            f.write_char('|')?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * The grid with the weights, a line per row, in the format
+ * `State::from_string` reads
+ */
+pub struct DebugOutput<'a, W = u32>(pub &'a State<W>);
+impl<W: Weight> Display for DebugOutput<'_, W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (y, row) in self.0.cells.chunks(self.0.width.max(1)).enumerate() {
+            if y > 0 {
+                f.write_char('\n')?;
+            }
+            for cell in row {
+                match cell {
+                    Cell::Empty => write!(f, "_"),
+                    Cell::Stationary(w) => write!(f, "{w}"),
+                    Cell::InMotion(Direction::Up, w) => write!(f, "^{w}"),
+                    Cell::InMotion(Direction::Down, w) => write!(f, "v{w}"),
+                    Cell::InMotion(Direction::Left, w) => write!(f, "-{w}"),
+                    Cell::InMotion(Direction::Right, w) => write!(f, "+{w}"),
+                }?;
+                f.write_char(' ')?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::super::{Classic, Elastic, Merge};
+    use super::*;
+
+    fn run(rule: &dyn CollisionRule, raw: &str, generations: usize) -> Vec<String> {
+        let mut state: State = State::from_string(raw).unwrap();
+        let mut grids = vec![DebugOutput(&state).to_string()];
+        for _ in 0..generations {
+            match state.next(rule) {
+                Some(next) => state = next,
+                None => break,
+            }
+            grids.push(DebugOutput(&state).to_string());
+        }
+        grids
+    }
+
+    #[test]
+    fn round_trips() {
+        let raw = "+3 _ v7\n_ ^12 0\n-1 _ 4 ";
+        let state: State = State::from_string(raw).unwrap();
+        assert_eq!((state.width(), state.height()), (3, 3));
+        assert_eq!(*state.cell(1, 1), Cell::InMotion(Direction::Up, 12));
+        assert_eq!(
+            DebugOutput(&state).to_string(),
+            "+3 _ v7 \n_ ^12 0 \n-1 _ 4 "
+        );
+        assert_eq!(state.to_string(), "|>-v|\n|-^o|\n|<-o|");
+
+        let semicolons: State = State::from_string(" +3 _ v7; _ ^12 0;-1 _ 4;").unwrap();
+        assert_eq!(semicolons, state);
+    }
+
+    #[test]
+    fn head_on_is_like_a_row() {
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "_ v5 _;_ _ _;_ ^3 _", 2),
+            ["_ v5 _ \n_ _ _ \n_ ^3 _ ", "_ _ _ \n_ v5 _ \n_ _ _ "]
+        );
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "+2 _ -2", 2),
+            ["+2 _ -2 ", "_ _ _ "]
+        );
+    }
+
+    #[test]
+    fn side_impacts() {
+        // Running into the side of something lighter
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "_ _ _;+2 _ _;_ ^9 _", 1),
+            ["_ _ _ \n+2 _ _ \n_ ^9 _ ", "_ _ _ \n_ ^9 _ \n_ _ _ "]
+        );
+        // Running into the side of something heavier, which carries on its way
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "_ v9 _;+2 _ _", 1),
+            ["_ v9 _ \n+2 _ _ ", "_ _ _ \n_ v9 _ "]
+        );
+        // Heavier than what's crossing in front
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "_ _ _;+9 v2 _", 1),
+            ["_ _ _ \n+9 v2 _ ", "_ _ _ \n_ +9 _ "]
+        );
+        // Lighter, so what it hit keeps going down, even after taking damage
+        let damage = Classic {
+            bounce: false,
+            partial_destroy: true,
+        };
+        assert_eq!(
+            run(&damage, "+2 v9 _;_ _ _", 1),
+            ["+2 v9 _ \n_ _ _ ", "_ _ _ \n_ v7 _ "]
+        );
+    }
+
+    #[test]
+    fn walls_and_bumping() {
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "^1 _;_ +2", 1),
+            ["^1 _ \n_ +2 ", "1 _ \n_ 2 "]
+        );
+        let bounce = Classic {
+            bounce: true,
+            partial_destroy: false,
+        };
+        assert_eq!(
+            run(&bounce, "_ ^2;v1 _", 1),
+            ["_ ^2 \nv1 _ ", "_ v2 \n^1 _ "]
+        );
+        // The one in front is shoved along rather than hit
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "+3 +1 _ 5", 1)[1],
+            "_ +3 +1 5 "
+        );
+        assert_eq!(run(&Merge { bounce: false }, "v2;v3;4", 2)[2], "_ \n_ \n9 ");
+    }
+
+    #[test]
+    fn stops_when_nothing_else_can_collide() {
+        assert_eq!(run(&Classic::WINNER_TAKES_ALL, "_ ^1;^2 _", 5).len(), 1);
+        assert_eq!(run(&Classic::WINNER_TAKES_ALL, "3 _;_ 4", 5).len(), 1);
+        assert_eq!(run(&Classic::WINNER_TAKES_ALL, "", 5).len(), 1);
+        // Going different ways without ever meeting, until they stop at the
+        // walls
+        assert_eq!(
+            run(&Classic::WINNER_TAKES_ALL, "_ _ _;_ _ _;+1 _ ^1", 5).len(),
+            4
+        );
+    }
+
+    #[test]
+    fn bouncing_can_go_on_forever() {
+        // Round and round a corner, never all going the same way
+        let bounce = Classic {
+            bounce: true,
+            partial_destroy: false,
+        };
+        let grids = run(&bounce, "+1 _;^1 _", 4);
+        assert_eq!(grids[4], grids[0]);
+    }
+
+    #[test]
+    fn never_gains_mass() {
+        let mut rng = StdRng::seed_from_u64(0x2d);
+        for _ in 0..50 {
+            let mut state: State = State::random(12, 8, 10, 30, 60, &mut rng);
+            let mut generations = 0;
+            while let Some(next) = state.next(&Classic::WINNER_TAKES_ALL) {
+                assert!(next.mass() <= state.mass());
+                state = next;
+                generations += 1;
+                assert!(generations < 100, "{}", DebugOutput(&state));
+            }
+            // Elastic never loses any either
+            let mut state: State = State::random(12, 8, 10, 30, 60, &mut rng);
+            let mass = state.mass();
+            for _ in 0..20 {
+                state = state.next(&Elastic).unwrap_or(state);
+                assert_eq!(state.mass(), mass);
+            }
+        }
+    }
+
+    #[test]
+    fn points_at_bad_cells() {
+        let error = |raw| State::<u32>::from_string(raw).unwrap_err();
+        assert_eq!(
+            error("_ _;_ x"),
+            ParseError {
+                kind: ParseErrorKind::UnexpectedCharacter('x'),
+                offset: 6,
+                cell: 3
+            }
+        );
+        assert_eq!(error("_ 3^").kind, ParseErrorKind::MisplacedSign('^'));
+        assert_eq!(error("_ v^3").kind, ParseErrorKind::TwoSigns);
+        assert_eq!(
+            error("_ 3@2").kind,
+            ParseErrorKind::UnexpectedCharacter('@')
+        );
+        assert_eq!(
+            error("_ _; _"),
+            ParseError {
+                kind: ParseErrorKind::UnevenRow {
+                    expected: 2,
+                    found: 1
+                },
+                offset: 5,
+                cell: 2
+            }
+        );
+    }
+}
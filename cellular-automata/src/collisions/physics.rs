@@ -0,0 +1,300 @@
+use std::cell::RefCell;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{Cell, Classic, CollisionResult, CollisionRule, Moving, Weight, hit_wall};
+
+/**
+ * Same as `Moving`, but pointing the other way
+ */
+fn reversed<W: Weight>(moving: &Moving<W>) -> Moving<W> {
+    match moving {
+        Moving::Left(w) => Moving::Right(*w),
+        Moving::Right(w) => Moving::Left(*w),
+    }
+}
+
+/**
+ * A new object going the same way as `moving`
+ */
+fn same_way<W: Weight>(moving: &Moving<W>, weight: W) -> Moving<W> {
+    match moving {
+        Moving::Left(_) => Moving::Left(weight),
+        Moving::Right(_) => Moving::Right(weight),
+    }
+}
+
+/**
+ * The moves every rule agrees on: into empty cells, and bumping along
+ * anything going the same way
+ */
+fn uncontested<W: Weight>(moving: &Moving<W>, destination: &Cell<W>) -> Option<CollisionResult<W>> {
+    match (moving, destination) {
+        (m, Cell::Empty) => Some(CollisionResult::MovingWon(m.clone())),
+        (m @ Moving::Left(_), Cell::InMotion(Moving::Left(d)))
+        | (m @ Moving::Right(_), Cell::InMotion(Moving::Right(d))) => {
+            Some(CollisionResult::DestinationEscapes {
+                bumped: *d,
+                moved: m.clone(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/**
+ * The weights of the two objects in a contested move
+ */
+fn weights<W: Weight>(moving: &Moving<W>, destination: &Cell<W>) -> (W, W) {
+    let (Moving::Left(m) | Moving::Right(m)) = moving;
+    match destination {
+        Cell::Stationary(d) | Cell::InMotion(Moving::Left(d) | Moving::Right(d)) => (*m, *d),
+        Cell::Empty => unreachable!("moves into empty cells are uncontested"),
+    }
+}
+
+/**
+ * Nothing is ever destroyed, objects swap velocities instead
+ *
+ * Since everything moves at the same speed this is what an elastic collision
+ * between equal weights does, like a Newton's cradle: two objects meeting head
+ * on both turn around, and an object hitting a stationary one stops and sets
+ * that one moving. Walls always bounce.
+ *
+ * Rules only see weights and directions though, so objects keep their own
+ * speeds through a collision, which isn't quite right when they differ.
+ */
+pub struct Elastic;
+
+impl<W: Weight> CollisionRule<W> for Elastic {
+    fn collide(&self, moving: &Moving<W>, destination: &Cell<W>) -> CollisionResult<W> {
+        if let Some(result) = uncontested(moving, destination) {
+            return result;
+        }
+        let (m, d) = weights(moving, destination);
+        match destination {
+            Cell::InMotion(other) => CollisionResult::Rebound {
+                moving: Cell::InMotion(reversed(moving)),
+                destination: Cell::InMotion(reversed(other)),
+            },
+            _ => CollisionResult::Rebound {
+                moving: Cell::Stationary(m),
+                destination: Cell::InMotion(same_way(moving, d)),
+            },
+        }
+    }
+
+    fn hit_wall(&self, moving: &Moving<W>) -> Cell<W> {
+        hit_wall(true, moving)
+    }
+}
+
+/**
+ * Colliding objects stick together, carrying on in the direction of the
+ * heavier one, or stopping if they're evenly matched. Anything heavier than
+ * `Weight::MAX` is lost.
+ */
+pub struct Merge {
+    pub bounce: bool,
+}
+
+impl<W: Weight> CollisionRule<W> for Merge {
+    fn collide(&self, moving: &Moving<W>, destination: &Cell<W>) -> CollisionResult<W> {
+        if let Some(result) = uncontested(moving, destination) {
+            return result;
+        }
+        let (m, d) = weights(moving, destination);
+        let merged = m.combined(d);
+        match destination {
+            _ if m > d => CollisionResult::MovingWon(same_way(moving, merged)),
+            Cell::InMotion(other) if m < d => {
+                CollisionResult::DestinationWon(Cell::InMotion(same_way(other, merged)))
+            }
+            _ => CollisionResult::DestinationWon(Cell::Stationary(merged)),
+        }
+    }
+
+    fn hit_wall(&self, moving: &Moving<W>) -> Cell<W> {
+        hit_wall(self.bounce, moving)
+    }
+}
+
+/**
+ * Winner takes all, but the lighter object can still win, with a chance
+ * proportional to its weight
+ *
+ * The RNG is behind a `RefCell` since rules only get `&self`, so this can't be
+ * shared between threads, but then a shared RNG wouldn't be reproducible
+ * anyway.
+ */
+pub struct Probabilistic<R: Rng> {
+    pub bounce: bool,
+    rng: RefCell<R>,
+}
+
+impl<R: Rng> Probabilistic<R> {
+    pub fn new(bounce: bool, rng: R) -> Probabilistic<R> {
+        Probabilistic {
+            bounce,
+            rng: RefCell::new(rng),
+        }
+    }
+}
+
+impl<W: Weight, R: Rng> CollisionRule<W> for Probabilistic<R> {
+    fn collide(&self, moving: &Moving<W>, destination: &Cell<W>) -> CollisionResult<W> {
+        if let Some(result) = uncontested(moving, destination) {
+            return result;
+        }
+        let (m, d) = weights(moving, destination);
+        if m == W::ZERO && d == W::ZERO {
+            return CollisionResult::BothDestroyed;
+        }
+        // Written this way round so huge weights can't add up to infinity
+        let chance = 1.0 / (1.0 + d.to_f64() / m.to_f64());
+        if self.rng.borrow_mut().gen_bool(chance) {
+            CollisionResult::MovingWon(moving.clone())
+        } else {
+            CollisionResult::DestinationWon(destination.clone())
+        }
+    }
+
+    fn hit_wall(&self, moving: &Moving<W>) -> Cell<W> {
+        hit_wall(self.bounce, moving)
+    }
+}
+
+/**
+ * The rules that come with the collider, for picking one by name
+ */
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Physics {
+    /// The heavier object wins, see --damage
+    #[default]
+    Classic,
+    /// Nothing is destroyed, objects swap velocities instead. Always bounces off the walls
+    Elastic,
+    /// Colliding objects stick together and carry on the way the heavier one was going
+    Merge,
+    /// Winner takes all, but the lighter object wins with a chance proportional to its weight
+    Probabilistic,
+}
+
+impl Physics {
+    /**
+     * The rule itself. `partial_destroy` only means anything to `Classic`,
+     * `bounce` to everything but `Elastic`, and `rng` is only used by
+     * `Probabilistic`.
+     */
+    pub fn rule<W: Weight, R: Rng + 'static>(
+        self,
+        bounce: bool,
+        partial_destroy: bool,
+        rng: R,
+    ) -> Box<dyn CollisionRule<W>> {
+        match self {
+            Physics::Classic => Box::new(Classic {
+                bounce,
+                partial_destroy,
+            }),
+            Physics::Elastic => Box::new(Elastic),
+            Physics::Merge => Box::new(Merge { bounce }),
+            Physics::Probabilistic => Box::new(Probabilistic::new(bounce, rng)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{DebugOutput, State};
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn run(rule: &dyn CollisionRule, raw: &str, generations: usize) -> Vec<String> {
+        let mut state = State::from_string(raw).unwrap();
+        let mut rows = vec![DebugOutput(&state).to_string()];
+        for _ in 0..generations {
+            match state.next(rule) {
+                Some(next) => state = next,
+                None => break,
+            }
+            rows.push(DebugOutput(&state).to_string());
+        }
+        rows
+    }
+
+    #[test]
+    fn elastic_swaps_velocities() {
+        assert_eq!(
+            run(&Elastic, "+3 _ _ -5", 3),
+            ["+3 _ _ -5 ", "_ +3 -5 _ ", "_ -3 +5 _ ", "-3 _ _ +5 "]
+        );
+        // Newton's cradle
+        assert_eq!(
+            run(&Elastic, "+4 _ 6 _", 3),
+            ["+4 _ 6 _ ", "_ +4 6 _ ", "_ 4 +6 _ ", "_ 4 _ +6 "]
+        );
+    }
+
+    #[test]
+    fn elastic_conserves_mass() {
+        let mut rng = StdRng::seed_from_u64(0xe1a5);
+        let mut state: State = State::random(60, 20, 20, 20, 40, &mut rng);
+        let mass = state.mass();
+        for _ in 0..200 {
+            state = state.next(&Elastic).unwrap_or(state);
+            assert_eq!(state.mass(), mass);
+        }
+    }
+
+    #[test]
+    fn merge_sums_weights() {
+        let merge = Merge { bounce: false };
+        assert_eq!(
+            merge.collide(&Moving::Right(5u32), &Cell::InMotion(Moving::Left(3))),
+            CollisionResult::MovingWon(Moving::Right(8))
+        );
+        assert_eq!(
+            merge.collide(&Moving::Left(3u32), &Cell::Stationary(5)),
+            CollisionResult::DestinationWon(Cell::Stationary(8))
+        );
+        assert_eq!(
+            merge.collide(&Moving::Left(4u32), &Cell::InMotion(Moving::Right(4))),
+            CollisionResult::DestinationWon(Cell::Stationary(8))
+        );
+        assert_eq!(
+            merge.collide(&Moving::Right(200u8), &Cell::InMotion(Moving::Left(70))),
+            CollisionResult::MovingWon(Moving::Right(u8::MAX))
+        );
+    }
+
+    #[test]
+    fn probabilistic_follows_the_seed() {
+        let outcomes = |seed| {
+            let rule = Probabilistic::new(false, StdRng::seed_from_u64(seed));
+            (0..100)
+                .map(|_| rule.collide(&Moving::Right(30u32), &Cell::Stationary(60)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(outcomes(1), outcomes(1));
+        let wins = outcomes(1)
+            .iter()
+            .filter(|r| matches!(r, CollisionResult::MovingWon(_)))
+            .count();
+        assert!((15..50).contains(&wins), "{wins} wins");
+
+        // Something with no weight never wins
+        let rule = Probabilistic::new(false, StdRng::seed_from_u64(1));
+        for _ in 0..100 {
+            assert_eq!(
+                rule.collide(&Moving::Left(0u32), &Cell::Stationary(1)),
+                CollisionResult::DestinationWon(Cell::Stationary(1))
+            );
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+/**
+ * How fast an object goes, `cells` every `generations`, so `3` is three cells a
+ * generation and `1/2` is one cell every other generation
+ *
+ * The default is one cell a generation, which is what everything did before
+ * speeds were added.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Speed {
+    pub cells: u8,
+    pub generations: u8,
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed {
+            cells: 1,
+            generations: 1,
+        }
+    }
+}
+
+impl Speed {
+    /**
+     * How many cells it goes a generation, on average
+     */
+    pub fn cells_per_generation(self) -> f64 {
+        f64::from(self.cells) / f64::from(self.generations)
+    }
+}
+
+impl FromStr for Speed {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let number = |raw: &str| {
+            raw.parse::<u8>()
+                .map_err(|e| format!("Invalid speed ({raw}): {e}"))
+                .and_then(|n| match n {
+                    0 => Err("Speed must be more than 0".to_string()),
+                    n => Ok(n),
+                })
+        };
+        let (cells, generations) = match raw.split_once('/') {
+            Some((cells, generations)) => (number(cells)?, number(generations)?),
+            None => (number(raw)?, 1),
+        };
+        Ok(Speed { cells, generations })
+    }
+}
+
+impl Display for Speed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.generations {
+            1 => write!(f, "{}", self.cells),
+            generations => write!(f, "{}/{generations}", self.cells),
+        }
+    }
+}
+
+/**
+ * Everything about an object's movement that isn't in its `Cell`, which
+ * travels along with the object
+ *
+ * `progress` is how far it's got towards its next move, in
+ * `1 / speed.generations` of a cell, and `moves` is how many cells it's
+ * allowed this generation.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Motion {
+    pub speed: Speed,
+    pub progress: u8,
+    pub moves: u8,
+}
+
+impl Motion {
+    pub fn new(speed: Speed) -> Motion {
+        Motion {
+            speed,
+            ..Motion::default()
+        }
+    }
+
+    /**
+     * Works out `moves` for a new generation. Only objects that are actually
+     * moving build up progress, but stationary ones get `moves` too in case
+     * they're set moving partway through.
+     */
+    pub fn start_generation(&mut self, in_motion: bool) {
+        let due = u16::from(self.progress) + u16::from(self.speed.cells);
+        let per = u16::from(self.speed.generations);
+        self.moves = (due / per) as u8;
+        if in_motion {
+            self.progress = (due % per) as u8;
+        }
+    }
+
+    /**
+     * A generation is split into as many sub-steps as the fastest object has
+     * moves, and this spreads this object's moves evenly across them, so
+     * everything is only ever one cell from where it was a sub-step ago
+     */
+    pub fn moves_during(&self, sub_step: u8, sub_steps: u8) -> bool {
+        let moved_by = |step: u8| u16::from(step) * u16::from(self.moves) / u16::from(sub_steps);
+        moved_by(sub_step) > moved_by(sub_step - 1)
+    }
+}
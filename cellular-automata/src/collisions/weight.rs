@@ -0,0 +1,281 @@
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+use rand::Rng;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/**
+ * What the weights of objects in the collider are measured in
+ *
+ * Everything the physics does to weights goes through here, so none of it can
+ * overflow: comparing them, the difference between two, and two added
+ * together, which stops at `MAX`. Weights are never negative, the direction
+ * is kept separately.
+ */
+pub trait Weight:
+    Copy
+    + PartialOrd
+    + Debug
+    + Display
+    + FromStr<Err: Display>
+    + Serialize
+    + DeserializeOwned
+    + Send
+    + Sync
+    + 'static
+{
+    const ZERO: Self;
+
+    /**
+     * The heaviest anything can get
+     */
+    const MAX: Self;
+
+    /**
+     * How much heavier one is than the other, whichever way round they are
+     */
+    fn difference(self, other: Self) -> Self;
+
+    /**
+     * Both weights together, or `MAX` if that's too heavy
+     */
+    fn combined(self, other: Self) -> Self;
+
+    /**
+     * For adding up totals and working out chances, which don't need to be
+     * exact
+     */
+    fn to_f64(self) -> f64;
+
+    /**
+     * A weight for an object in a random state, a whole number between 1 and
+     * 99 whatever the type
+     */
+    fn random<R: Rng + ?Sized>(r: &mut R) -> Self;
+
+    /**
+     * The nearest weight to `value`, so between `ZERO` and `MAX`, and whole
+     * for the integer types. NaN counts as nothing.
+     */
+    fn from_f64(value: f64) -> Self;
+
+    /**
+     * Whether something that parsed is actually a weight
+     */
+    fn is_valid(self) -> bool {
+        self >= Self::ZERO && self <= Self::MAX
+    }
+}
+
+macro_rules! integer_weight {
+    ($($t:ty),*) => {
+        $(
+            impl Weight for $t {
+                const ZERO: Self = 0;
+                const MAX: Self = <$t>::MAX;
+
+                fn difference(self, other: Self) -> Self {
+                    self.abs_diff(other)
+                }
+
+                fn combined(self, other: Self) -> Self {
+                    self.saturating_add(other)
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn random<R: Rng + ?Sized>(r: &mut R) -> Self {
+                    r.gen_range(1i8..100) as Self
+                }
+
+                fn from_f64(value: f64) -> Self {
+                    // Float to int casts saturate, and NaN goes to 0
+                    value.round() as Self
+                }
+            }
+        )*
+    };
+}
+
+integer_weight!(u8, u16, u32, u64);
+
+/**
+ * Any finite weight that isn't negative, so no NaNs or infinities
+ */
+impl Weight for f64 {
+    const ZERO: Self = 0.0;
+    const MAX: Self = f64::MAX;
+
+    fn difference(self, other: Self) -> Self {
+        (self - other).abs()
+    }
+
+    fn combined(self, other: Self) -> Self {
+        (self + other).min(f64::MAX)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn random<R: Rng + ?Sized>(r: &mut R) -> Self {
+        r.gen_range(1i8..100).into()
+    }
+
+    fn from_f64(value: f64) -> Self {
+        if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, f64::MAX)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::super::{
+        Classic, CollisionRule, DebugOutput, Elastic, Merge, Probabilistic, Speed, State,
+    };
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    /**
+     * A field of `(kind, weight)` pairs, where kind 0 is empty, 1 stationary,
+     * 2 left and 3 right
+     */
+    fn field<W: Weight>(cells: &[(u8, W)]) -> State<W> {
+        let raw: Vec<String> = cells
+            .iter()
+            .map(|(kind, w)| match kind {
+                0 => "_".to_string(),
+                1 => format!("{w}"),
+                2 => format!("-{w}"),
+                _ => format!("+{w}"),
+            })
+            .collect();
+        State::from_string(&raw.join(" ")).unwrap()
+    }
+
+    /**
+     * Runs every rule over the field until it settles or has had long enough,
+     * checking nothing ever gets heavier
+     */
+    fn run_everything<W: Weight>(state: &State<W>) {
+        let rules: Vec<Box<dyn CollisionRule<W>>> = vec![
+            Box::new(Classic::WINNER_TAKES_ALL),
+            Box::new(Classic {
+                bounce: true,
+                partial_destroy: true,
+            }),
+            Box::new(Elastic),
+            Box::new(Merge { bounce: true }),
+            Box::new(Probabilistic::new(true, StdRng::seed_from_u64(0))),
+        ];
+        for rule in &rules {
+            let mut state = state.clone();
+            for _ in 0..3 * state.cells().len() {
+                let Some(next) = state.next(rule.as_ref()) else {
+                    break;
+                };
+                state = next;
+            }
+        }
+        let classic = Classic {
+            bounce: false,
+            partial_destroy: true,
+        };
+        let mut state = state.clone();
+        while let Some(next) = state.next(&classic) {
+            assert!(next.mass() <= state.mass());
+            state = next;
+        }
+    }
+
+    /**
+     * Prints a state and parses it back, checking nothing changed, speeds
+     * included
+     */
+    fn round_trip<W: Weight>(cells: &[(u8, W)], speeds: &[Speed]) -> Result<(), TestCaseError> {
+        let raw: Vec<String> = cells
+            .iter()
+            .zip(speeds)
+            .map(|((kind, w), speed)| match kind {
+                0 => "_".to_string(),
+                1 => format!("{w}@{speed}"),
+                2 => format!("-{w}@{speed}"),
+                _ => format!("+{w}@{speed}"),
+            })
+            .collect();
+        let state: State<W> = State::from_string(&raw.join(" ")).unwrap();
+        let printed = DebugOutput(&state).to_string();
+        let parsed: State<W> = State::from_string(&printed).unwrap();
+        prop_assert_eq!(parsed.cells(), state.cells());
+        for index in 0..state.cells().len() {
+            prop_assert_eq!(parsed.speed(index), state.speed(index));
+        }
+        Ok(())
+    }
+
+    fn speeds() -> impl Strategy<Value = Vec<Speed>> {
+        prop::collection::vec(
+            (1..=u8::MAX, 1..=u8::MAX)
+                .prop_map(|(cells, generations)| Speed { cells, generations }),
+            24,
+        )
+    }
+
+    fn cells<W: Weight + Debug>(
+        weight: impl Strategy<Value = W>,
+    ) -> impl Strategy<Value = Vec<(u8, W)>> {
+        prop::collection::vec((0..4u8, weight), 1..24)
+    }
+
+    proptest! {
+        #[test]
+        fn u8_never_overflows(cells in cells(any::<u8>())) {
+            run_everything(&field(&cells));
+        }
+
+        #[test]
+        fn u32_never_overflows(cells in cells(any::<u32>())) {
+            run_everything(&field(&cells));
+        }
+
+        #[test]
+        fn f64_never_overflows(cells in cells(0.0..=f64::MAX)) {
+            run_everything(&field(&cells));
+        }
+
+        #[test]
+        fn debug_output_round_trips(cells in cells(any::<u32>()), speeds in speeds()) {
+            round_trip(&cells, &speeds)?;
+        }
+
+        #[test]
+        fn debug_output_round_trips_u8(cells in cells(any::<u8>()), speeds in speeds()) {
+            round_trip(&cells, &speeds)?;
+        }
+
+        #[test]
+        fn debug_output_round_trips_f64(cells in cells(0.0..=f64::MAX), speeds in speeds()) {
+            round_trip(&cells, &speeds)?;
+        }
+    }
+
+    #[test]
+    fn saturates_at_the_edges() {
+        assert_eq!(200u8.combined(100), u8::MAX);
+        assert_eq!(0u8.difference(u8::MAX), u8::MAX);
+        assert_eq!(u32::MAX.difference(0), u32::MAX);
+        assert_eq!(f64::MAX.combined(f64::MAX), f64::MAX);
+        assert!(!f64::NAN.is_valid());
+        assert!(!f64::INFINITY.is_valid());
+        assert!(!(-1.0).is_valid());
+    }
+}
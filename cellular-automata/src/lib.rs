@@ -0,0 +1,9 @@
+pub mod batch;
+pub mod collisions;
+pub mod life;
+pub mod render;
+pub mod run_file;
+pub mod sample;
+pub mod simple;
+pub mod survey;
+pub mod tui;
@@ -0,0 +1,402 @@
+use std::fmt::{Display, Write};
+
+/**
+ * A Life-like rule, written in B/S notation: https://conwaylife.com/wiki/Rulestring
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    birth: [bool; 9],
+    survival: [bool; 9],
+}
+impl Rule {
+    pub fn life() -> Rule {
+        Rule::from_string("B3/S23").expect("Conway's rule should parse")
+    }
+
+    /**
+     * Understands things like `B3/S23` and `b36/s23`, in either order
+     */
+    pub fn from_string(raw: &str) -> Result<Rule, String> {
+        let mut birth: Option<[bool; 9]> = None;
+        let mut survival: Option<[bool; 9]> = None;
+        for part in raw.trim().split('/') {
+            let mut chars = part.chars();
+            let target = match chars.next() {
+                Some('B' | 'b') => &mut birth,
+                Some('S' | 's') => &mut survival,
+                _ => return Err(format!("Expected a rule like B3/S23, got '{raw}'")),
+            };
+            if target.is_some() {
+                return Err(format!("Rule '{raw}' has more than one B or S part"));
+            }
+            let mut counts = [false; 9];
+            for c in chars {
+                match c.to_digit(10) {
+                    Some(d) if d <= 8 => counts[d as usize] = true,
+                    _ => return Err(format!("Unexpected '{c}' in rule '{raw}'")),
+                }
+            }
+            *target = Some(counts);
+        }
+        match (birth, survival) {
+            (Some(birth), Some(survival)) => Ok(Rule { birth, survival }),
+            _ => Err(format!("Rule '{raw}' needs both a B and an S part")),
+        }
+    }
+
+    fn apply(&self, alive: bool, neighbours: usize) -> bool {
+        if alive {
+            self.survival[neighbours]
+        } else {
+            self.birth[neighbours]
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_char('B')?;
+        for (n, _) in self.birth.iter().enumerate().filter(|(_, b)| **b) {
+            write!(f, "{n}")?;
+        }
+        f.write_str("/S")?;
+        for (n, _) in self.survival.iter().enumerate().filter(|(_, s)| **s) {
+            write!(f, "{n}")?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * What happens at the edges of the grid
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Topology {
+    /// Opposite edges are joined, so things leaving one side come back on the other
+    Toroidal,
+    /// Everything outside the grid is dead
+    Bounded,
+}
+
+/**
+ * The most cells a pattern file can ask for. Anything bigger is almost
+ * certainly a mistake, and would take a silly amount of memory to build.
+ */
+const MAX_PATTERN_CELLS: usize = 1 << 28;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    cells: Vec<bool>,
+}
+
+impl Grid {
+    pub fn new(width: usize, height: usize) -> Grid {
+        Grid {
+            width,
+            height,
+            cells: vec![false; width * height],
+        }
+    }
+
+    /**
+     * Parses the plaintext format used by `.cells` files: https://conwaylife.com/wiki/Plaintext
+     *
+     * Lines starting with '!' are comments, 'O' is alive and '.' is dead.
+     * Lines can be shorter than the widest one, the rest is dead.
+     */
+    pub fn from_plaintext(raw: &str) -> Result<Grid, String> {
+        let rows: Vec<&str> = raw
+            .lines()
+            .map(|line| line.trim_end())
+            .filter(|line| !line.starts_with('!'))
+            .collect();
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut grid = Grid::new(width, rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    'O' | '*' => grid.set(x, y, true),
+                    '.' => (),
+                    _ => {
+                        return Err(format!(
+                            "Unexpected character '{c}' on line {} of the pattern",
+                            y + 1
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(grid)
+    }
+
+    /**
+     * Parses run-length encoded patterns: https://conwaylife.com/wiki/Run_Length_Encoded
+     *
+     * Returns the rule too, if the header had one.
+     */
+    pub fn from_rle(raw: &str) -> Result<(Grid, Option<Rule>), String> {
+        let mut lines = raw
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        let header = lines.next().ok_or("RLE pattern is missing its header")?;
+        let mut width: Option<usize> = None;
+        let mut height: Option<usize> = None;
+        let mut rule: Option<Rule> = None;
+        for field in header.split(',') {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or_else(|| format!("Malformed RLE header: '{header}'"))?;
+            let value = value.trim();
+            match key.trim() {
+                "x" => width = value.parse().ok(),
+                "y" => height = value.parse().ok(),
+                "rule" => rule = Some(Rule::from_string(value)?),
+                _ => (),
+            }
+        }
+        let (width, height) = width
+            .zip(height)
+            .ok_or_else(|| format!("RLE header needs a width and height: '{header}'"))?;
+        if width
+            .checked_mul(height)
+            .is_none_or(|cells| cells > MAX_PATTERN_CELLS)
+        {
+            return Err(format!("RLE pattern is too big: {width}x{height}"));
+        }
+
+        let mut grid = Grid::new(width, height);
+        let (mut x, mut y) = (0usize, 0usize);
+        let mut count: Option<usize> = None;
+        'body: for line in lines {
+            for c in line.chars() {
+                match c {
+                    _ if c.is_ascii_digit() => {
+                        let digit = c.to_digit(10).unwrap_or(0) as usize;
+                        count = count
+                            .unwrap_or(0)
+                            .checked_mul(10)
+                            .and_then(|n| n.checked_add(digit));
+                        if count.is_none() {
+                            return Err(format!("Run count too long in RLE pattern: '{line}'"));
+                        }
+                    }
+                    'b' | 'o' => {
+                        let run = count.take().unwrap_or(1);
+                        if x.checked_add(run).is_none_or(|end| end > width) || y >= height {
+                            return Err(format!(
+                                "RLE pattern doesn't fit in its {width}x{height} header"
+                            ));
+                        }
+                        if c == 'o' {
+                            for dx in 0..run {
+                                grid.set(x + dx, y, true);
+                            }
+                        }
+                        x += run;
+                    }
+                    '$' => {
+                        y = y.saturating_add(count.take().unwrap_or(1));
+                        x = 0;
+                    }
+                    '!' => break 'body,
+                    _ if c.is_whitespace() => (),
+                    _ => return Err(format!("Unexpected character '{c}' in RLE pattern")),
+                }
+            }
+        }
+        Ok((grid, rule))
+    }
+
+    /**
+     * Copies this grid into the middle of a new, larger one
+     */
+    pub fn centred_in(&self, width: usize, height: usize) -> Result<Grid, String> {
+        if self.width > width || self.height > height {
+            return Err(format!(
+                "A {}x{} pattern doesn't fit in a {width}x{height} grid",
+                self.width, self.height
+            ));
+        }
+        let dx = (width - self.width) / 2;
+        let dy = (height - self.height) / 2;
+        let mut grid = Grid::new(width, height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                grid.set(x + dx, y + dy, self.get(x, y));
+            }
+        }
+        Ok(grid)
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.cells[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, alive: bool) {
+        self.cells[y * self.width + x] = alive;
+    }
+
+    fn neighbours(&self, x: usize, y: usize, topology: Topology) -> usize {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let mut count = 0;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if dx == 0 && dy == 0 {
+                    continue;
+                }
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                let alive = match topology {
+                    Topology::Toroidal => self.get(
+                        nx.rem_euclid(width) as usize,
+                        ny.rem_euclid(height) as usize,
+                    ),
+                    Topology::Bounded => {
+                        (0..width).contains(&nx)
+                            && (0..height).contains(&ny)
+                            && self.get(nx as usize, ny as usize)
+                    }
+                };
+                if alive {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    /**
+     * Calculates the next generation
+     */
+    pub fn next(&self, rule: &Rule, topology: Topology) -> Grid {
+        let cells = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| rule.apply(self.get(x, y), self.neighbours(x, y, topology)))
+            .collect();
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells,
+        }
+    }
+}
+
+impl Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.cells.chunks(self.width.max(1)) {
+            f.write_char('|')?;
+            for c in row {
+                f.write_char(if *c { 'X' } else { ' ' })?;
+            }
+            f.write_str("|\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "!Name: Glider\n.O.\n..O\nOOO\n";
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(Rule::from_string("B3/S23").unwrap(), Rule::life());
+        assert_eq!(Rule::from_string("s23/b3").unwrap(), Rule::life());
+        assert_eq!(Rule::from_string("B36/S23").unwrap().to_string(), "B36/S23");
+        assert_eq!(Rule::from_string("B/S").unwrap().to_string(), "B/S");
+        assert!(Rule::from_string("23/3").is_err());
+        assert!(Rule::from_string("B9/S23").is_err());
+        assert!(Rule::from_string("B3").is_err());
+        assert!(Rule::from_string("B3/B3").is_err());
+    }
+
+    #[test]
+    fn parses_plaintext() {
+        let glider = Grid::from_plaintext(GLIDER).unwrap();
+        assert_eq!(glider.to_string(), "| X |\n|  X|\n|XXX|\n");
+        assert!(Grid::from_plaintext("..x").is_err());
+    }
+
+    #[test]
+    fn parses_rle() {
+        let (glider, rule) =
+            Grid::from_rle("#N Glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+        assert_eq!(glider, Grid::from_plaintext(GLIDER).unwrap());
+        assert_eq!(rule, Some(Rule::life()));
+
+        // Runs of '$' skip rows, and lines can be split anywhere
+        let (gap, rule) = Grid::from_rle("x = 2, y = 3\n2o2$\nbo!").unwrap();
+        assert_eq!(gap.to_string(), "|XX|\n|  |\n| X|\n");
+        assert_eq!(rule, None);
+
+        assert!(Grid::from_rle("x = 2, y = 1\n3o!").is_err());
+        assert!(Grid::from_rle("bo$2bo$3o!").is_err());
+        // Counts too big for a usize, or that would go past the end of one
+        assert!(Grid::from_rle("x = 2, y = 1\n99999999999999999999999o!").is_err());
+        assert!(Grid::from_rle("x = 2, y = 1\nbo18446744073709551615o!").is_err());
+        assert!(Grid::from_rle("x = 2, y = 1\n18446744073709551615$bo!").is_err());
+        // Or a header asking for more cells than there's any sense building
+        assert!(Grid::from_rle("x = 99999999999, y = 99999999999\no!").is_err());
+        assert!(Grid::from_rle("x = 100000, y = 100000\no!").is_err());
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let blinker = Grid::from_plaintext(".....\n.....\n.OOO.\n.....\n.....").unwrap();
+        let next = blinker.next(&Rule::life(), Topology::Bounded);
+        assert_eq!(
+            next,
+            Grid::from_plaintext(".....\n..O..\n..O..\n..O..\n.....").unwrap()
+        );
+        assert_eq!(next.next(&Rule::life(), Topology::Bounded), blinker);
+    }
+
+    #[test]
+    fn glider_wraps_around_a_torus() {
+        let start = Grid::from_plaintext(GLIDER)
+            .unwrap()
+            .centred_in(8, 8)
+            .unwrap();
+        let mut grid = start.clone();
+        // A glider moves one cell diagonally every four generations
+        for _ in 0..32 {
+            grid = grid.next(&Rule::life(), Topology::Toroidal);
+            assert_eq!(grid.cells.iter().filter(|c| **c).count(), 5);
+        }
+        assert_eq!(grid, start);
+    }
+
+    #[test]
+    fn glider_stops_at_the_edge() {
+        let mut grid = Grid::from_plaintext(GLIDER)
+            .unwrap()
+            .centred_in(6, 6)
+            .unwrap();
+        for _ in 0..32 {
+            grid = grid.next(&Rule::life(), Topology::Bounded);
+        }
+        // Turns into a block in the corner
+        assert_eq!(
+            grid,
+            Grid::from_plaintext("......\n......\n......\n......\n....OO\n....OO").unwrap()
+        );
+    }
+
+    #[test]
+    fn highlife_differs_from_life() {
+        // Six neighbours is a birth in HighLife, but not in Life
+        let grid = Grid::from_plaintext("OOO\n...\nOOO").unwrap();
+        let highlife = Rule::from_string("B36/S23").unwrap();
+        assert!(grid.next(&highlife, Topology::Toroidal).get(1, 1));
+        assert!(!grid.next(&Rule::life(), Topology::Toroidal).get(1, 1));
+    }
+}
@@ -1,9 +1,12 @@
-mod collisions;
-mod simple;
-
 use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
 
+use cellular_automata::{batch, collisions, life, render, run_file, sample, simple, survey, tui};
 use clap::{Args, Parser, Subcommand};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -19,6 +22,14 @@ enum Commands {
     Simple(SimpleArgs),
     /// Run a cellular automaton that simulates collisions
     Collider(ColliderArgs),
+    /// Run a collider on a grid, with objects moving up, down, left and right
+    Collider2d(Collider2dArgs),
+    /// Run lots of random collider fields and show how they turned out
+    ColliderBatch(ColliderBatchArgs),
+    /// Run a two-dimensional Life-like automaton
+    Life(LifeArgs),
+    /// Run lots of elementary rules and measure how each of them behaves
+    Survey(SurveyArgs),
 }
 
 #[derive(Args)]
@@ -26,16 +37,105 @@ struct SimpleArgs {
     #[arg(
         short = 'r',
         long,
-        help = "Wolfram code for the automaton",
+        help = "Wolfram code for the automaton, or the code of the generalised rule if any of the options below are used",
         default_value_t = 154
     )]
-    rule: u8,
+    rule: u128,
+    #[arg(
+        long,
+        help = "Number of cells on each side of the centre cell that the rule looks at",
+        default_value_t = 1
+    )]
+    radius: usize,
+    #[arg(
+        short = 'k',
+        long,
+        help = "Number of colours a cell can be, in the initial state these are the digits 0 to 9",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u8).range(2..=10)
+    )]
+    colours: u8,
+    #[arg(
+        long,
+        help = "The rule only looks at the sum of the colours in the neighbourhood",
+        default_value_t = false,
+        conflicts_with = "outer_totalistic"
+    )]
+    totalistic: bool,
+    #[arg(
+        long,
+        help = "The rule looks at the centre cell and the sum of the colours around it",
+        default_value_t = false
+    )]
+    outer_totalistic: bool,
+    #[arg(
+        long,
+        help = "Print the rules that are the same as this one with left and right or live and dead swapped, instead of running it",
+        default_value_t = false,
+        conflicts_with_all = ["radius", "colours", "totalistic", "outer_totalistic"]
+    )]
+    show_equivalents: bool,
+    #[arg(
+        long,
+        help = "Find the rows that turn into the initial state in one generation, instead of running it. Only works for Wolfram codes with periodic boundaries",
+        default_value_t = false,
+        conflicts_with_all = ["radius", "colours", "totalistic", "outer_totalistic", "show_equivalents", "reversible"]
+    )]
+    predecessors: bool,
     #[arg(
         short = 's',
         long = "state",
-        help = "Initial state, true is any non-whitespace character"
+        help = "Initial state, whitespace is colour 0, digits are that colour and any other character is colour 1"
     )]
     initial_state: Option<String>,
+    #[arg(
+        long,
+        help = "Start from a random state instead",
+        default_value_t = false,
+        conflicts_with = "initial_state"
+    )]
+    random: bool,
+    #[arg(
+        long,
+        help = "Width of the random initial state, in cells",
+        default_value_t = 121,
+        requires = "random"
+    )]
+    width: usize,
+    #[arg(
+        long,
+        help = "Chance of each cell in the random initial state being alive, between 0 and 1",
+        default_value_t = 0.5,
+        value_parser = parse_density,
+        requires = "random"
+    )]
+    density: f64,
+    #[arg(
+        long,
+        help = "Seed for the random initial state, so the same one can be generated again. Picked at random and printed after the run if not given",
+        requires = "random"
+    )]
+    seed: Option<u64>,
+    #[arg(
+        short = 'b',
+        long,
+        help = "What the cells past the ends of the row look like",
+        value_enum,
+        default_value_t = simple::Boundary::Periodic
+    )]
+    boundary: simple::Boundary,
+    #[arg(
+        long,
+        help = "Make the rule second-order: each row is the rule applied to the one before, minus the one before that. This makes any rule reversible",
+        default_value_t = false
+    )]
+    reversible: bool,
+    #[arg(
+        long,
+        help = "Row before the initial state for --reversible, the same width as it. Defaults to all dead",
+        requires = "reversible"
+    )]
+    previous: Option<String>,
     #[arg(
         short = 'g',
         long = "generations",
@@ -43,28 +143,134 @@ struct SimpleArgs {
         default_value_t = 32
     )]
     generations: u32,
+    #[arg(
+        long,
+        help = "Stop as soon as a generation repeats an earlier one. --generations still limits the run, but 0 means no limit",
+        default_value_t = false
+    )]
+    until_cycle: bool,
+    #[arg(
+        long,
+        help = "Save the run to a file before running it, so it can be run again with --load-run. JSON or TOML, depending on the extension",
+        value_parser = parse_run_path,
+        conflicts_with_all = ["show_equivalents", "predecessors"]
+    )]
+    save_run: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Run what was saved to a file with --save-run, instead of the options above",
+        value_parser = parse_run_path,
+        conflicts_with_all = ["rule", "radius", "colours", "totalistic", "outer_totalistic", "show_equivalents", "initial_state", "random", "boundary", "reversible", "generations", "until_cycle"]
+    )]
+    load_run: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Play the generations in the terminal instead of printing them, with keys to pause, step back and forward, scroll and change the speed",
+        default_value_t = false,
+        conflicts_with_all = ["path", "output", "every", "only_final", "show_equivalents", "predecessors"]
+    )]
+    tui: bool,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    image: ImageArgs,
 }
 
 #[derive(Args)]
 struct ColliderArgs {
+    #[command(flatten)]
+    physics: PhysicsArgs,
+    #[command(flatten)]
+    initial_state: ColliderStateArgs,
+    #[command(flatten)]
+    random_generation_args: ColliderStateRandomArgs,
+    #[arg(
+        short = 'g',
+        long = "generations",
+        help = "Number of generations to run, 0 means run until no more collisions are possible",
+        default_value_t = 0
+    )]
+    generations: u32,
     #[arg(
-        short = 'b',
         long,
-        help = "Do cells bounce off the ends of the field, or just come to a stop",
+        help = "Print each generation in a more verbose format, showing the weights as well as the directions",
         default_value_t = false
     )]
-    bounce: bool,
+    debug: bool,
     #[arg(
-        short = 'd',
-        long = "damage",
-        help = "Do cells that collide take damage, or are collisions 'winner takes all'",
+        long,
+        help = "Print a summary of the collisions, mass destroyed and momentum at the end",
         default_value_t = false
     )]
-    partial_destroy: bool,
+    stats: bool,
+    #[arg(
+        long,
+        help = "Write the collisions, mass destroyed and momentum for each generation to a CSV file"
+    )]
+    stats_csv: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Write every move and collision to a file, as one JSON object per line"
+    )]
+    events: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Save the run to a file before running it, so it can be run again with --load-run. JSON or TOML, depending on the extension",
+        value_parser = parse_run_path
+    )]
+    save_run: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Play the generations in the terminal instead of printing them, with keys to pause, step back and forward, scroll and change the speed",
+        default_value_t = false,
+        conflicts_with_all = ["path", "output", "every", "only_final"]
+    )]
+    tui: bool,
     #[command(flatten)]
-    initial_state: ColliderStateArgs,
+    output: OutputArgs,
     #[command(flatten)]
-    random_generation_args: ColliderStateRandomArgs,
+    image: ImageArgs,
+}
+
+#[derive(Args)]
+struct Collider2dArgs {
+    #[command(flatten)]
+    physics: PhysicsArgs,
+    #[command(flatten)]
+    initial_state: Collider2dStateArgs,
+    #[arg(
+        long = "rand-width",
+        help = "Random initial state width, in cells. Ignored if state is specified",
+        default_value_t = 40
+    )]
+    random_state_width: usize,
+    #[arg(
+        long = "rand-height",
+        help = "Random initial state height, in cells. Ignored if state is specified",
+        default_value_t = 20
+    )]
+    random_state_height: usize,
+    #[arg(
+        long = "rand-stationary",
+        help = "Weighted chance of a stationary object. Ignored if state is specified",
+        default_value_t = 20,
+        value_parser = clap::value_parser!(u8).range(0..100)
+    )]
+    random_chance_stationary: u8,
+    #[arg(
+        long = "rand-moving",
+        help = "Weighted chance of a moving object, going any of the four ways. Ignored if state is specified",
+        default_value_t = 20,
+        value_parser = clap::value_parser!(u8).range(0..100)
+    )]
+    random_chance_moving: u8,
+    #[arg(
+        long = "rand-empty",
+        help = "Weighted chance of an empty cell. Ignored if state is specified",
+        default_value_t = 60,
+        value_parser = clap::value_parser!(u8).range(0..100)
+    )]
+    random_chance_empty: u8,
     #[arg(
         short = 'g',
         long = "generations",
@@ -78,6 +284,202 @@ struct ColliderArgs {
         default_value_t = false
     )]
     debug: bool,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Args)]
+struct ColliderBatchArgs {
+    #[command(flatten)]
+    physics: PhysicsArgs,
+    #[command(flatten)]
+    random_generation_args: ColliderStateRandomArgs,
+    #[arg(
+        short = 'n',
+        long,
+        help = "Number of random fields to run",
+        default_value_t = 1000
+    )]
+    runs: u64,
+    #[arg(
+        short = 'g',
+        long = "generations",
+        help = "Most generations to run each field for, 0 means run until no more collisions are possible",
+        default_value_t = 0
+    )]
+    generations: u64,
+    #[arg(
+        short = 'j',
+        long,
+        help = "Number of threads to run fields on, defaults to one for each CPU",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    threads: Option<usize>,
+    #[arg(
+        long,
+        help = "Write the survivors, generations and mass destroyed for every field to a CSV file"
+    )]
+    csv: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Most bars in each histogram",
+        default_value_t = 10,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    bins: usize,
+}
+
+/**
+ * What all the colliders have in common, how objects collide and what's
+ * around the field
+ */
+#[derive(Args, Clone)]
+struct PhysicsArgs {
+    #[arg(
+        short = 'b',
+        long,
+        help = "Do cells bounce off the walls, or just come to a stop",
+        default_value_t = false
+    )]
+    bounce: bool,
+    #[arg(
+        short = 't',
+        long,
+        help = "What's past the ends of the field. Grids only have walls",
+        value_enum,
+        default_value_t = collisions::Topology::Walls
+    )]
+    topology: collisions::Topology,
+    #[arg(
+        short = 'd',
+        long = "damage",
+        help = "Do cells that collide take damage, or are collisions 'winner takes all'. Only for the classic physics",
+        default_value_t = false
+    )]
+    partial_destroy: bool,
+    #[arg(
+        short = 'p',
+        long,
+        help = "What happens when objects collide",
+        value_enum,
+        default_value_t = collisions::Physics::Classic
+    )]
+    physics: collisions::Physics,
+    #[arg(
+        long,
+        help = "Seed for the random initial state and the probabilistic physics, so the same run can be had again. With --state it only affects the probabilistic physics. collider-batch gives each field after the first the next seed along. Picked at random and printed after the run if not given"
+    )]
+    seed: Option<u64>,
+}
+
+impl PhysicsArgs {
+    /**
+     * Checks the options make sense together, and that a run of 0
+     * `generations`, which goes until nothing else can collide, will end
+     */
+    fn validate(&self, generations: u64, grid: bool) -> Result<(), String> {
+        if self.partial_destroy && self.physics != collisions::Physics::Classic {
+            return Err("--damage only works with the classic physics".to_string());
+        }
+        if self.topology == collisions::Topology::Ring {
+            if grid {
+                return Err("Grids can't be rings yet, they only have walls".to_string());
+            }
+            if self.bounce {
+                return Err(
+                    "--bounce doesn't do anything on a ring, there are no walls".to_string()
+                );
+            }
+        }
+        if generations == 0 {
+            // Nothing's ever destroyed and everything bounces, so it could go
+            // on forever
+            if self.physics == collisions::Physics::Elastic {
+                return Err("Elastic collisions need a number of --generations".to_string());
+            }
+            // On a row everything that bounces eventually runs into something,
+            // but on a grid it can go back and forth forever out of the way
+            // of everything going across it
+            if grid && self.bounce {
+                return Err("--bounce needs a number of --generations on a grid".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * The rule, with its own stream of the seed for the probabilistic physics
+     */
+    fn rule(&self, seed: u64) -> Box<dyn collisions::CollisionRule> {
+        self.physics
+            .rule(self.bounce, self.partial_destroy, collision_rng(seed))
+    }
+}
+
+#[derive(Args, Clone)]
+#[group(required = true, multiple = false)]
+struct Collider2dStateArgs {
+    #[arg(
+        short = 's',
+        long = "state",
+        help = "Initial state, rows of cells like the collider's separated by ';' or newlines, all the same length. Objects moving up are prefixed with '^' and objects moving down with 'v'. There are no speeds",
+        value_parser = parse_collider2d_state
+    )]
+    state_string: Option<collisions::grid::State>,
+    #[arg(
+        required = true,
+        short = 'r',
+        long = "random",
+        help = "Generate a random initial state"
+    )]
+    random: bool,
+}
+
+#[derive(Args, Clone)]
+struct OutputArgs {
+    #[arg(
+        short = 'o',
+        long,
+        help = "Write the output to a file instead of stdout"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Only write every Nth generation, starting with the first. The last generation is always written",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    every: u64,
+    #[arg(
+        long,
+        help = "Only write the last generation",
+        default_value_t = false,
+        conflicts_with = "every"
+    )]
+    only_final: bool,
+}
+
+#[derive(Args, Clone)]
+struct ImageArgs {
+    #[arg(
+        long = "image",
+        help = "Draw the generations to an image instead of printing them, one row of cells per generation. The format comes from the extension: .png, .ppm or .pbm",
+        value_parser = parse_image_path
+    )]
+    path: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Width and height of each cell in the image, in pixels",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    cell_size: u16,
+    #[arg(
+        long,
+        help = "Comma separated colours for the image, like '#ffffff,#000000'. For simple these are the colours of cells 0, 1, 2 and so on. For collider they are empty, stationary, left-moving and right-moving, faded by weight",
+        value_delimiter = ','
+    )]
+    palette: Vec<render::Rgb>,
 }
 
 #[derive(Args, Clone)]
@@ -86,8 +488,8 @@ struct ColliderStateArgs {
     #[arg(
         short = 's',
         long = "state", 
-        help = "Initial state, an empty cell is a '_', filled cells are numbers between 99 and 99, prefixed with a '-' for leftward movement or '+' for rightward movement and unprefixed if stationary. Each cell must be separated by a space",
-        value_parser = collisions::State::from_string
+        help = "Initial state, an empty cell is a '_', filled cells are weights between 0 and 4294967295, prefixed with a '-' for leftward movement or '+' for rightward movement and unprefixed if stationary. Objects can end with '@' and a speed, like '+3@2' or '-3@1/4', in cells per generation. Each cell must be separated by a space",
+        value_parser = parse_collider_state
     )]
     state_string: Option<collisions::State>,
     #[arg(
@@ -97,6 +499,13 @@ struct ColliderStateArgs {
         help = "Generate a random initial state"
     )]
     random: bool,
+    #[arg(
+        long,
+        help = "Run what was saved to a file with --save-run, instead of using the options that describe the run",
+        value_parser = parse_run_path,
+        conflicts_with_all = ["bounce", "topology", "partial_destroy", "physics", "generations", "seed"]
+    )]
+    load_run: Option<PathBuf>,
 }
 
 #[derive(Args, Clone)]
@@ -135,84 +544,935 @@ struct ColliderStateRandomArgs {
         value_parser = clap::value_parser!(u8).range(0..100)
     )]
     random_chance_empty: u8,
+    #[arg(
+        long = "rand-counts",
+        help = "Exact numbers of stationary, left-moving and right-moving objects, like '5,10,10', shuffled into an otherwise empty field. Used instead of the weighted chances. Ignored if state is specified"
+    )]
+    random_counts: Option<collisions::Counts>,
+    #[arg(
+        long = "rand-weights-stationary",
+        help = "How the weights of stationary objects are drawn: 'standard' for whole numbers from 1 to 99, 'uniform:LOW,HIGH', 'normal:MEAN,DEVIATION' or 'power-law:EXPONENT'. Ignored if state is specified",
+        default_value_t = collisions::Distribution::Standard
+    )]
+    random_weights_stationary: collisions::Distribution,
+    #[arg(
+        long = "rand-weights-left",
+        help = "How the weights of left-moving objects are drawn, like --rand-weights-stationary. Ignored if state is specified",
+        default_value_t = collisions::Distribution::Standard
+    )]
+    random_weights_left: collisions::Distribution,
+    #[arg(
+        long = "rand-weights-right",
+        help = "How the weights of right-moving objects are drawn, like --rand-weights-stationary. Ignored if state is specified",
+        default_value_t = collisions::Distribution::Standard
+    )]
+    random_weights_right: collisions::Distribution,
+    #[arg(
+        long = "rand-mirror",
+        help = "Only make the left half of the field random, and the right half its mirror image with everything going the other way. An odd width leaves the middle cell empty. Ignored if state is specified",
+        default_value_t = false
+    )]
+    random_mirror: bool,
+}
+
+#[derive(Args)]
+struct LifeArgs {
+    #[arg(
+        short = 'r',
+        long,
+        help = "Rule in B/S notation, like B3/S23 or B36/S23. Defaults to the rule in the pattern file, or B3/S23 if there isn't one"
+    )]
+    rule: Option<String>,
+    #[arg(
+        short = 'p',
+        long,
+        help = "Pattern file to start from, either RLE (.rle) or plaintext (.cells). Defaults to a glider"
+    )]
+    pattern: Option<PathBuf>,
+    #[arg(
+        short = 'x',
+        long,
+        help = "Width of the grid, the pattern is placed in the middle",
+        default_value_t = 40
+    )]
+    width: usize,
+    #[arg(
+        short = 'y',
+        long,
+        help = "Height of the grid, the pattern is placed in the middle",
+        default_value_t = 20
+    )]
+    height: usize,
+    #[arg(
+        short = 't',
+        long,
+        help = "What happens at the edges of the grid",
+        value_enum,
+        default_value_t = life::Topology::Toroidal
+    )]
+    topology: life::Topology,
+    #[arg(
+        short = 'g',
+        long = "generations",
+        help = "Number of generations to run",
+        default_value_t = 8
+    )]
+    generations: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum TableFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Args)]
+struct SurveyArgs {
+    #[arg(
+        short = 'r',
+        long,
+        help = "Comma separated Wolfram codes to survey, defaults to all 256 of them",
+        value_delimiter = ','
+    )]
+    rules: Vec<u8>,
+    #[arg(
+        long,
+        help = "Only survey the rule with the smallest code out of each set that are mirror images or complements of each other",
+        default_value_t = false
+    )]
+    distinct: bool,
+    #[arg(
+        short = 's',
+        long = "state",
+        help = "Initial state to run each rule from, can be given more than once. Whitespace is dead and anything else is alive"
+    )]
+    initial_states: Vec<String>,
+    #[arg(
+        long,
+        help = "Number of random initial states to run each rule from, as well as any given with --state",
+        default_value_t = 0
+    )]
+    random: usize,
+    #[arg(
+        short = 'x',
+        long,
+        help = "Width of the random initial states, and of the single cell one used if there are no others",
+        default_value_t = 101
+    )]
+    width: usize,
+    #[arg(
+        long,
+        help = "Seed for the random initial states, so the same ones can be generated again. Picked at random and printed to stderr if not given"
+    )]
+    seed: Option<u64>,
+    #[arg(
+        short = 'b',
+        long,
+        help = "What the cells past the ends of the row look like",
+        value_enum,
+        default_value_t = simple::Boundary::Periodic
+    )]
+    boundary: simple::Boundary,
+    #[arg(
+        short = 'g',
+        long = "generations",
+        help = "Most generations to run each rule for, runs stop early once they repeat",
+        default_value_t = 200
+    )]
+    generations: usize,
+    #[arg(
+        short = 'f',
+        long,
+        help = "Output format. CSV leaves out the density of each generation",
+        value_enum,
+        default_value_t = TableFormat::Csv
+    )]
+    format: TableFormat,
+}
+
+/**
+ * Past this, `simple --predecessors` just gives the count
+ */
+const MAX_LISTED_PREDECESSORS: u128 = 256;
+
+fn parse_density(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(density) if (0.0..=1.0).contains(&density) => Ok(density),
+        _ => Err(format!("Expected a number between 0 and 1, got '{raw}'")),
+    }
+}
+
+/**
+ * Shows the state back with the problem pointed out, since they can get long
+ */
+fn parse_collider_state(raw: &str) -> Result<collisions::State, String> {
+    collisions::State::from_string(raw).map_err(|e| e.diagnostic(raw))
+}
+
+fn parse_collider2d_state(raw: &str) -> Result<collisions::grid::State, String> {
+    collisions::grid::State::from_string(raw).map_err(|e| e.diagnostic(raw))
+}
+
+impl ColliderStateRandomArgs {
+    /**
+     * A random state, however the options say to make it
+     */
+    fn generate(&self, rng: &mut ChaCha8Rng) -> Result<collisions::State, String> {
+        let weights = collisions::Weights {
+            stationary: self.random_weights_stationary,
+            left: self.random_weights_left,
+            right: self.random_weights_right,
+        };
+        let width = match self.random_mirror {
+            true => self.random_state_width / 2,
+            false => self.random_state_width,
+        };
+        let state = match self.random_counts {
+            Some(counts) => collisions::State::with_counts(width, counts, &weights, rng)?,
+            None => collisions::State::random_with(
+                width,
+                self.random_chance_stationary.into(),
+                self.random_chance_left.into(),
+                self.random_chance_right.into(),
+                self.random_chance_empty.into(),
+                &weights,
+                rng,
+            ),
+        };
+        Ok(match self.random_mirror {
+            true => state.mirrored(self.random_state_width % 2),
+            false => state,
+        })
+    }
+}
+
+/**
+ * Makes an RNG from the seed, or a random one if there isn't one, and hands
+ * back the seed so it can be printed for next time
+ */
+fn seeded_rng(seed: Option<u64>) -> (u64, ChaCha8Rng) {
+    let seed = seed.unwrap_or_else(rand::random);
+    (seed, ChaCha8Rng::seed_from_u64(seed))
+}
+
+/**
+ * The RNG for the probabilistic physics. It's a different stream to the one
+ * random initial states come from, so a saved run, which has the state itself
+ * rather than how to generate it, gets the same collisions when it's loaded.
+ */
+fn collision_rng(seed: u64) -> ChaCha8Rng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(1);
+    rng
+}
+
+/**
+ * Where the output goes. It's buffered either way, since a long run can be
+ * millions of lines.
+ */
+fn open_output(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn parse_run_path(raw: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(raw);
+    run_file::Format::from_path(&path)?;
+    Ok(path)
+}
+
+fn parse_image_path(raw: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(raw);
+    render::Format::from_path(&path)?;
+    Ok(path)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    match Cli::parse().command {
+    match run(Cli::parse()) {
+        // Whatever was reading the output has stopped, like `head` does once
+        // it has enough lines, so there's no one to complain to
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
         Commands::Simple(SimpleArgs {
             rule,
+            radius,
+            colours,
+            totalistic,
+            outer_totalistic,
+            show_equivalents,
+            predecessors,
             initial_state,
+            random,
+            width,
+            density,
+            seed,
+            boundary,
+            reversible,
+            previous,
             generations,
+            until_cycle,
+            save_run,
+            load_run,
+            tui,
+            output,
+            image,
         }) => {
-            let rule = simple::Wolfram::from_code(rule);
-            let state = initial_state.unwrap_or_else(|| format!("{0}X{0}", " ".repeat(60)));
-            let mut state = simple::State::from_string(&state);
-            if generations == 0 {
-                println!("{state}");
+            let mut out = open_output(output.output.as_deref())?;
+            if show_equivalents {
+                let code = u8::try_from(rule)
+                    .map_err(|_| format!("Wolfram codes go from 0 to 255, got {rule}"))?;
+                let rule = simple::Wolfram::from_code(code);
+                writeln!(out, "Mirror: {}", rule.mirror().code())?;
+                writeln!(out, "Complement: {}", rule.complement().code())?;
+                writeln!(
+                    out,
+                    "Mirror complement: {}",
+                    rule.mirror_complement().code()
+                )?;
+                writeln!(out, "Canonical: {}", rule.canonical().code())?;
+                out.flush()?;
+                return Ok(());
             }
-            for _ in 0..generations {
-                println!("|{state}|");
-                state = state.next(&rule);
+            let run = match load_run {
+                Some(path) => run_file::RunFile::load(&path)?.into_simple()?,
+                None => {
+                    let neighbourhood = if totalistic {
+                        simple::Neighbourhood::Totalistic
+                    } else if outer_totalistic {
+                        simple::Neighbourhood::OuterTotalistic
+                    } else {
+                        simple::Neighbourhood::Full
+                    };
+                    let (state, seed) = if random {
+                        let (seed, mut rng) = seeded_rng(seed);
+                        let state = simple::State::random(width, colours, density, &mut rng);
+                        (state.to_string(), Some(seed))
+                    } else {
+                        let state =
+                            initial_state.unwrap_or_else(|| format!("{0}X{0}", " ".repeat(60)));
+                        (state, None)
+                    };
+                    run_file::SimpleRun {
+                        rule,
+                        radius,
+                        colours,
+                        neighbourhood,
+                        boundary,
+                        state,
+                        reversible,
+                        previous,
+                        seed,
+                        generations,
+                        until_cycle,
+                    }
+                }
+            };
+            if let Some(path) = save_run {
+                run_file::RunFile::Simple(run.clone()).save(&path)?;
             }
+            let run_file::SimpleRun {
+                rule,
+                radius,
+                colours,
+                neighbourhood,
+                boundary,
+                state,
+                reversible,
+                previous,
+                seed,
+                generations,
+                until_cycle,
+            } = run;
+            let state = simple::State::from_string(&state);
+            if state.max_colour() >= colours {
+                return Err(format!(
+                    "Initial state uses colour {}, but the rule only has {colours} colours",
+                    state.max_colour()
+                )
+                .into());
+            }
+            if predecessors {
+                if boundary != simple::Boundary::Periodic {
+                    return Err("Predecessors can only be found with periodic boundaries".into());
+                }
+                let code = u8::try_from(rule)
+                    .map_err(|_| format!("Wolfram codes go from 0 to 255, got {rule}"))?;
+                let rule = simple::Wolfram::from_code(code);
+                match state.count_predecessors(&rule) {
+                    0 => writeln!(
+                        out,
+                        "Orphan: nothing turns into this state under rule {code}"
+                    )?,
+                    1 => writeln!(out, "|{}|\n1 predecessor", state.predecessors(&rule)[0])?,
+                    count if count > MAX_LISTED_PREDECESSORS => {
+                        writeln!(out, "{count} predecessors, too many to list")?
+                    }
+                    count => {
+                        for predecessor in state.predecessors(&rule) {
+                            writeln!(out, "|{predecessor}|")?;
+                        }
+                        writeln!(out, "{count} predecessors")?;
+                    }
+                }
+                out.flush()?;
+                return Ok(());
+            }
+            if generations == 0 && !until_cycle {
+                writeln!(out, "{state}")?;
+            }
+            // The elementary rules get to use the bit-packed state, which is
+            // much faster, everything else makes do with a byte per cell
+            let elementary =
+                radius == 1 && colours == 2 && neighbourhood == simple::Neighbourhood::Full;
+            let previous = match previous {
+                Some(previous) => simple::State::from_string(&previous),
+                None => simple::State::new(&vec![0; state.cells().len()]),
+            };
+            // A second-order run only repeats once a pair of rows in a row does
+            let mut last_row = reversible.then(|| previous.clone());
+            let rows: Box<dyn Iterator<Item = simple::State>> = if reversible {
+                let rule = simple::Code::new(rule, radius, colours, neighbourhood)?;
+                let run = simple::Reversible::new(previous, state, boundary)?;
+                Box::new(
+                    std::iter::successors(Some(run), move |r| Some(r.forward(&rule)))
+                        .map(|r| r.current().clone()),
+                )
+            } else if elementary {
+                let code = u8::try_from(rule)
+                    .map_err(|_| format!("Wolfram codes go from 0 to 255, got {rule}"))?;
+                let rule = simple::Wolfram::from_code(code);
+                let state = simple::Packed::from_state(&state);
+                Box::new(
+                    std::iter::successors(Some(state), move |s| Some(s.next(&rule, boundary)))
+                        .map(|s| s.to_state()),
+                )
+            } else {
+                let rule = simple::Code::new(rule, radius, colours, neighbourhood)?;
+                Box::new(std::iter::successors(Some(state), move |s| {
+                    Some(s.next(&rule, boundary))
+                }))
+            };
+            let limit = simple::generation_limit(boundary, generations as usize, until_cycle)?;
+            let mut detector = simple::CycleDetector::new();
+            let mut cycle: Option<simple::Cycle> = None;
+            let rows = rows.take(limit).map_while(|row| {
+                if cycle.is_some() {
+                    // Include the first repeat, so it's visible in the output
+                    return if until_cycle { None } else { Some(row) };
+                }
+                cycle = if reversible {
+                    let pair = (last_row.replace(row.clone()), row.clone());
+                    detector.observe(&pair)
+                } else {
+                    detector.observe(&row)
+                };
+                Some(row)
+            });
+
+            let mut diagram = image.path.as_ref().map(|_| {
+                render::Diagram::new(image.palette.first().copied().unwrap_or(render::Rgb::WHITE))
+            });
+            // `before` and `after` are how many blank cells to pad the row with
+            let mut emit = |before: usize, row: &simple::State, after: usize| match &mut diagram {
+                Some(diagram) => {
+                    let mut pixels = vec![diagram.background(); before];
+                    pixels.append(&mut render::simple_row(row, &image.palette));
+                    diagram.push(pixels);
+                    Ok(())
+                }
+                None => writeln!(out, "|{}{row}{}|", " ".repeat(before), " ".repeat(after)),
+            };
+            let sampler = sample::Sampler::new(output.every, output.only_final);
+            if tui {
+                let mut rows = rows;
+                if let Some(first) = rows.next() {
+                    tui::Viewer::new(first).run(
+                        |_| Ok::<_, std::io::Error>(rows.next()),
+                        |row| (row.origin(), format!("|{row}|")),
+                    )?;
+                }
+            } else if boundary == simple::Boundary::Growing {
+                // The rows get wider as they go, so we need all of them before
+                // we know how much padding each one needs to line up
+                let rows: Vec<simple::State> = sampler.sample(rows).collect();
+                let left = rows.iter().map(|r| r.origin()).min().unwrap_or(0);
+                let right = rows
+                    .iter()
+                    .map(|r| r.origin() + r.cells().len() as isize)
+                    .max()
+                    .unwrap_or(0);
+                for row in rows {
+                    let before = (row.origin() - left) as usize;
+                    let after = (right - left) as usize - before - row.cells().len();
+                    emit(before, &row, after)?;
+                }
+            } else {
+                for row in sampler.sample(rows) {
+                    emit(0, &row, 0)?;
+                }
+            }
+            if let (Some(diagram), Some(path)) = (diagram, image.path) {
+                diagram.save(&path, image.cell_size.into())?;
+            }
+            match cycle {
+                Some(simple::Cycle { transient, period }) => {
+                    writeln!(out, "Cycle: transient {transient}, period {period}")?
+                }
+                None if until_cycle => writeln!(out, "No repeats within {limit} generations")?,
+                None => (),
+            }
+            if let Some(seed) = seed {
+                writeln!(out, "Seed: {seed}")?;
+            }
+            out.flush()?;
             Ok(())
         }
         Commands::Collider(ColliderArgs {
             initial_state,
             generations,
-            bounce,
-            partial_destroy,
+            physics,
             debug,
+            stats,
+            stats_csv,
+            events,
+            save_run,
+            tui,
+            output,
             random_generation_args,
+            image,
         }) => {
-            let rule = collisions::Rule {
+            let random_state = initial_state.random;
+            let run = match initial_state.load_run {
+                Some(path) => run_file::RunFile::load(&path)?.into_collider()?,
+                None => {
+                    let (seed, mut rng) = seeded_rng(physics.seed);
+                    let state = match initial_state.state_string {
+                        Some(state) => state,
+                        None => random_generation_args.generate(&mut rng)?,
+                    };
+                    run_file::ColliderRun {
+                        physics: physics.physics,
+                        bounce: physics.bounce,
+                        damage: physics.partial_destroy,
+                        topology: physics.topology,
+                        state: collisions::DebugOutput(&state).to_string(),
+                        seed,
+                        generations,
+                    }
+                }
+            };
+            if let Some(path) = save_run {
+                run_file::RunFile::Collider(run.clone()).save(&path)?;
+            }
+            let run_file::ColliderRun {
+                physics,
                 bounce,
+                damage: partial_destroy,
+                topology,
+                state: initial,
+                seed,
+                generations,
+            } = run;
+            // Checked here rather than straight away, so loaded runs are too
+            let physics_args = PhysicsArgs {
+                bounce,
+                topology,
                 partial_destroy,
+                physics,
+                seed: Some(seed),
             };
-            let print_starting_state = initial_state.state_string.is_none();
-            let starting_state = initial_state.state_string.unwrap_or_else(|| {
-                collisions::State::random(
-                    random_generation_args.random_state_width,
-                    random_generation_args.random_chance_stationary.into(),
-                    random_generation_args.random_chance_left.into(),
-                    random_generation_args.random_chance_right.into(),
-                    random_generation_args.random_chance_empty.into(),
-                )
+            physics_args.validate(generations.into(), false)?;
+            let mut state = parse_collider_state(&initial)?.with_topology(topology);
+            // One seed covers both the initial state and the probabilistic
+            // collisions
+            let rule = physics_args.rule(seed);
+            let mut out = open_output(output.output.as_deref())?;
+            let mut diagram = image.path.as_ref().map(|_| {
+                render::Diagram::new(image.palette.first().copied().unwrap_or(render::Rgb::WHITE))
             });
-            let print = if debug {
-                |state: &collisions::State| {
-                    println!("{}", collisions::DebugOutput(state));
-                }
-            } else {
-                |state: &collisions::State| {
-                    println!("{state}");
+            let mut print = |state: &collisions::State| match &mut diagram {
+                Some(diagram) => {
+                    diagram.push(render::collider_row(state, &image.palette));
+                    Ok(())
                 }
+                None if debug => writeln!(out, "{}", collisions::DebugOutput(state)),
+                None => writeln!(out, "{state}"),
+            };
+            let mut stats_csv = match &stats_csv {
+                Some(path) => Some(collisions::StatsCsv::new(BufWriter::new(File::create(
+                    path,
+                )?))?),
+                None => None,
+            };
+            // Generation 0 doesn't have any collisions, but it's handy to have
+            // the starting momentum in the CSV
+            let first = collisions::Stats::of(&state);
+            if let Some(csv) = &mut stats_csv {
+                csv.write(&first)?;
+            }
+            let mut total = collisions::Stats::default();
+            total.accumulate(&first);
+            let mut generations_run = 0;
+            let mut event_log = match events {
+                Some(path) => Some(BufWriter::new(File::create(path)?)),
+                None => None,
             };
-            let mut state = starting_state.clone();
-            if generations == 0 {
-                print(&state);
-                while let Some(next_state) = state.next(&rule) {
-                    state = next_state;
-                    print(&state);
+            // Works out the next generation, keeping track of the stats and
+            // writing out the events as it goes, so nothing builds up over a
+            // long run
+            let mut step = |state: &collisions::State| -> Result<_, Box<dyn Error>> {
+                let mut stats = collisions::Stats::default();
+                let mut events: Vec<collisions::Event> = Vec::new();
+                let next = state.next_observed(
+                    rule.as_ref(),
+                    generations_run + 1,
+                    &mut (&mut stats, &mut events),
+                );
+                if let Some(out) = &mut event_log {
+                    for event in &events {
+                        serde_json::to_writer(&mut *out, event)?;
+                        writeln!(out)?;
+                    }
                 }
+                if next.is_some() {
+                    generations_run += 1;
+                    total.accumulate(&stats);
+                    if let Some(csv) = &mut stats_csv {
+                        csv.write(&stats)?;
+                    }
+                }
+                Ok(next)
+            };
+            if tui {
+                // As many rows as would have been printed
+                let mut rows = 1;
+                tui::Viewer::new(state).run(
+                    |state| {
+                        if generations != 0 && rows >= generations {
+                            return Ok(None);
+                        }
+                        rows += 1;
+                        step(state)
+                    },
+                    |state| match debug {
+                        true => (0, collisions::DebugOutput(state).to_string()),
+                        false => (0, state.to_string()),
+                    },
+                )?;
             } else {
-                for _ in 0..generations {
-                    print(&state);
-                    match state.next(&rule) {
-                        Some(new_state) => state = new_state,
-                        None => break,
+                let mut sampler = sample::Sampler::new(output.every, output.only_final);
+                let mut rows = 0;
+                loop {
+                    let next = step(&state)?;
+                    rows += 1;
+                    let last = next.is_none() || rows == generations;
+                    if sampler.take(last) {
+                        print(&state)?;
+                    }
+                    match next {
+                        Some(next) if !last => state = next,
+                        _ => break,
                     }
                 }
             }
-            if print_starting_state {
-                println!(
+            if let Some(mut out) = event_log {
+                out.flush()?;
+            }
+            if let (Some(diagram), Some(path)) = (diagram, image.path) {
+                diagram.save(&path, image.cell_size.into())?;
+            }
+            if let Some(mut csv) = stats_csv {
+                csv.flush()?;
+            }
+            if stats {
+                writeln!(out, "Generations        {:>8}", generations_run)?;
+                writeln!(out, "Collisions         {:>8}", total.collisions())?;
+                writeln!(out, "  Both destroyed   {:>8}", total.both_destroyed)?;
+                writeln!(out, "  Moving won       {:>8}", total.moving_won)?;
+                writeln!(out, "  Destination won  {:>8}", total.destination_won)?;
+                writeln!(out, "  Bumped along     {:>8}", total.destination_escapes)?;
+                writeln!(out, "  Rebounded        {:>8}", total.rebounds)?;
+                writeln!(out, "Mass destroyed     {:>8}", total.mass_destroyed)?;
+                writeln!(out, "Momentum left      {:>8}", total.momentum_left)?;
+                writeln!(out, "Momentum right     {:>8}", total.momentum_right)?;
+                writeln!(out, "Objects remaining  {:>8}", total.objects)?;
+            }
+            if random_state {
+                writeln!(
+                    out,
                     // The leading space is because clap has trouble dealing with
                     // argument values that start with '-' and I don't want to
                     // deal with that when copy-pasting initial states.
-                    "Initial state:\n' {}'",
-                    collisions::DebugOutput(&starting_state)
-                );
+                    "Initial state (seed {seed}):\n' {initial}'"
+                )?;
+            } else if physics == collisions::Physics::Probabilistic {
+                writeln!(out, "Seed: {seed}")?;
             }
+            out.flush()?;
             Ok(())
         }
+        Commands::Collider2d(Collider2dArgs {
+            physics,
+            initial_state,
+            random_state_width,
+            random_state_height,
+            random_chance_stationary,
+            random_chance_moving,
+            random_chance_empty,
+            generations,
+            debug,
+            output,
+        }) => {
+            physics.validate(generations.into(), true)?;
+            let (seed, mut rng) = seeded_rng(physics.seed);
+            let mut state = initial_state.state_string.unwrap_or_else(|| {
+                collisions::grid::State::random(
+                    random_state_width,
+                    random_state_height,
+                    random_chance_stationary.into(),
+                    random_chance_moving.into(),
+                    random_chance_empty.into(),
+                    &mut rng,
+                )
+            });
+            // Rows joined up so it can be pasted back into --state
+            let initial = collisions::grid::DebugOutput(&state)
+                .to_string()
+                .lines()
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("; ");
+            let rule = physics.rule(seed);
+            let mut out = open_output(output.output.as_deref())?;
+            let mut sampler = sample::Sampler::new(output.every, output.only_final);
+            let mut rows = 0;
+            loop {
+                let next = state.next(rule.as_ref());
+                rows += 1;
+                let last = next.is_none() || rows == generations;
+                if sampler.take(last) {
+                    match debug {
+                        true => writeln!(out, "{}\n", collisions::grid::DebugOutput(&state))?,
+                        false => writeln!(out, "{state}\n")?,
+                    }
+                }
+                match next {
+                    Some(next) if !last => state = next,
+                    _ => break,
+                }
+            }
+            if initial_state.random {
+                writeln!(out, "Initial state (seed {seed}):\n' {initial}'")?;
+            } else if physics.physics == collisions::Physics::Probabilistic {
+                writeln!(out, "Seed: {seed}")?;
+            }
+            out.flush()?;
+            Ok(())
+        }
+        Commands::ColliderBatch(ColliderBatchArgs {
+            physics,
+            random_generation_args,
+            runs,
+            generations,
+            threads,
+            csv,
+            bins,
+        }) => {
+            physics.validate(generations, false)?;
+            let first_seed = physics.seed.unwrap_or_else(rand::random);
+            let threads = threads
+                .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+            // Each field is exactly what `collider --random` makes from the
+            // same seed, so any of them can be run again on its own
+            let outcomes = batch::run(first_seed, runs, threads, generations, |seed| {
+                let (seed, mut rng) = seeded_rng(Some(seed));
+                let state = random_generation_args
+                    .generate(&mut rng)?
+                    .with_topology(physics.topology);
+                Ok((state, physics.rule(seed)))
+            })?;
+            if let Some(path) = csv {
+                let mut out = BufWriter::new(File::create(path)?);
+                batch::write_csv(&mut out, &outcomes)?;
+                out.flush()?;
+            }
+            let column = |f: fn(&batch::Outcome) -> f64| outcomes.iter().map(f).collect::<Vec<_>>();
+            let mut out = io::stdout().lock();
+            let histograms = [
+                ("Survivors", column(|o| o.survivors as f64)),
+                ("Generations", column(|o| o.generations as f64)),
+                ("Mass destroyed", column(|o| o.mass_destroyed)),
+            ];
+            for (title, values) in histograms {
+                writeln!(out, "{}", batch::Histogram::new(title, &values, bins))?;
+            }
+            let unfinished = outcomes.iter().filter(|o| !o.finished).count();
+            if unfinished > 0 {
+                writeln!(
+                    out,
+                    "{unfinished} fields were still colliding after {generations} generations"
+                )?;
+            }
+            writeln!(
+                out,
+                "Seeds: {first_seed} to {}",
+                first_seed.wrapping_add(runs.saturating_sub(1))
+            )?;
+            Ok(())
+        }
+        Commands::Life(LifeArgs {
+            rule,
+            pattern,
+            width,
+            height,
+            topology,
+            generations,
+        }) => {
+            let (pattern, pattern_rule) = match pattern {
+                Some(path) => {
+                    let raw = std::fs::read_to_string(&path)?;
+                    if path.extension().is_some_and(|e| e == "rle") {
+                        life::Grid::from_rle(&raw)?
+                    } else {
+                        (life::Grid::from_plaintext(&raw)?, None)
+                    }
+                }
+                None => (life::Grid::from_plaintext(".O.\n..O\nOOO")?, None),
+            };
+            let rule = match rule {
+                Some(rule) => life::Rule::from_string(&rule)?,
+                None => pattern_rule.unwrap_or_else(life::Rule::life),
+            };
+            let mut grid = pattern.centred_in(width, height)?;
+            for _ in 0..generations {
+                println!("{grid}");
+                grid = grid.next(&rule, topology);
+            }
+            Ok(())
+        }
+        Commands::Survey(SurveyArgs {
+            rules,
+            distinct,
+            initial_states,
+            random,
+            width,
+            seed,
+            boundary,
+            generations,
+            format,
+        }) => {
+            let mut rules: Vec<simple::Wolfram> = if rules.is_empty() {
+                (0..=255).map(simple::Wolfram::from_code).collect()
+            } else {
+                rules.into_iter().map(simple::Wolfram::from_code).collect()
+            };
+            if distinct {
+                rules.retain(survey::is_distinct);
+            }
+
+            let mut states: Vec<simple::State> = initial_states
+                .iter()
+                .map(|s| simple::State::from_string(s))
+                .collect();
+            if random > 0 {
+                let (seed, mut rng) = seeded_rng(seed);
+                eprintln!("Seed: {seed}");
+                for _ in 0..random {
+                    states.push(simple::State::random(width, 2, 0.5, &mut rng));
+                }
+            }
+            if states.is_empty() {
+                let mut cells = vec![0; width.max(1)];
+                cells[width / 2] = 1;
+                states.push(simple::State::new(&cells));
+            }
+
+            let metrics: Vec<survey::Metrics> = rules
+                .iter()
+                .flat_map(|rule| {
+                    states.iter().enumerate().map(move |(seed, state)| {
+                        survey::measure(rule, seed, state, boundary, generations)
+                    })
+                })
+                .collect();
+            let mut out = std::io::stdout().lock();
+            match format {
+                TableFormat::Csv => survey::write_csv(&mut out, &metrics)?,
+                TableFormat::Json => {
+                    serde_json::to_writer_pretty(&mut out, &metrics)?;
+                    writeln!(out)?;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+    use clap::error::ErrorKind;
+
+    use super::*;
+
+    #[test]
+    fn arguments_hang_together() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn tui_takes_over_the_output() {
+        for command in [&["simple"][..], &["collider", "-s", "+1 -1"]] {
+            for output in [
+                &["--every", "2"][..],
+                &["--only-final"],
+                &["--output", "out.txt"],
+            ] {
+                let args = ["cellular-automata"]
+                    .iter()
+                    .chain(command)
+                    .chain(&["--tui"])
+                    .chain(output);
+                let error = Cli::try_parse_from(args).err();
+                assert_eq!(
+                    error.map(|e| e.kind()),
+                    Some(ErrorKind::ArgumentConflict),
+                    "{command:?} {output:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn checks_the_physics() {
+        let physics = |args: &[&str]| {
+            let args = ["collider", "-r"].iter().chain(args);
+            match Cli::parse_from(["cellular-automata"].iter().chain(args)).command {
+                Commands::Collider(args) => args.physics,
+                _ => unreachable!(),
+            }
+        };
+        assert!(physics(&[]).validate(0, false).is_ok());
+        assert!(physics(&["-d", "-p", "merge"]).validate(0, false).is_err());
+        assert!(physics(&["-b", "-t", "ring"]).validate(0, false).is_err());
+        assert!(physics(&["-t", "ring"]).validate(0, true).is_err());
+        assert!(physics(&["-p", "elastic"]).validate(0, false).is_err());
+        assert!(physics(&["-p", "elastic"]).validate(10, false).is_ok());
+        // Only grids need a limit to bounce
+        assert!(physics(&["-b"]).validate(0, false).is_ok());
+        assert!(physics(&["-b"]).validate(0, true).is_err());
+        assert!(physics(&["-b"]).validate(10, true).is_ok());
     }
 }
@@ -0,0 +1,306 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::collisions::{self, Cell, Moving, Weight};
+use crate::simple;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+
+    /**
+     * Fades the colour towards white, `amount` of 1.0 is the full colour and
+     * 0.0 is white
+     */
+    pub fn shade(&self, amount: f64) -> Rgb {
+        let amount = amount.clamp(0.0, 1.0);
+        let fade = |c: u8| 255 - ((255 - c) as f64 * amount).round() as u8;
+        Rgb(fade(self.0), fade(self.1), fade(self.2))
+    }
+
+    fn is_dark(&self) -> bool {
+        // Rough perceived brightness, good enough to pick black or white
+        (299 * self.0 as u32 + 587 * self.1 as u32 + 114 * self.2 as u32) / 1000 < 128
+    }
+}
+
+/**
+ * Parses colours written like `#ff8800` or `ff8800`
+ */
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let hex = raw.trim().trim_start_matches('#');
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| format!("Expected a colour like #ff8800, got '{raw}'"))
+        };
+        if hex.len() != 6 {
+            return Err(format!("Expected a colour like #ff8800, got '{raw}'"));
+        }
+        Ok(Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+/**
+ * Default colours for the simple automaton, one per colour of cell
+ */
+pub const SIMPLE_PALETTE: [Rgb; 10] = [
+    Rgb::WHITE,
+    Rgb::BLACK,
+    Rgb(0xd6, 0x27, 0x28),
+    Rgb(0x1f, 0x77, 0xb4),
+    Rgb(0x2c, 0xa0, 0x2c),
+    Rgb(0xff, 0x7f, 0x0e),
+    Rgb(0x94, 0x67, 0xbd),
+    Rgb(0x8c, 0x56, 0x4b),
+    Rgb(0xe3, 0x77, 0xc2),
+    Rgb(0x17, 0xbe, 0xcf),
+];
+
+/**
+ * Default colours for the collider, in the order empty, stationary, left-moving
+ * and right-moving
+ */
+pub const COLLIDER_PALETTE: [Rgb; 4] = [
+    Rgb::WHITE,
+    Rgb(0x40, 0x40, 0x40),
+    Rgb(0x1f, 0x77, 0xb4),
+    Rgb(0xd6, 0x27, 0x28),
+];
+
+/**
+ * Colours a row of the simple automaton, anything missing from the palette
+ * falls back to the default one
+ */
+pub fn simple_row(state: &simple::State, palette: &[Rgb]) -> Vec<Rgb> {
+    state
+        .cells()
+        .iter()
+        .map(|c| {
+            let c = usize::from(*c);
+            palette.get(c).copied().unwrap_or(SIMPLE_PALETTE[c])
+        })
+        .collect()
+}
+
+/**
+ * Colours a row of the collider by direction, with lighter objects drawn
+ * fainter than heavy ones
+ */
+pub fn collider_row<W: Weight>(state: &collisions::State<W>, palette: &[Rgb]) -> Vec<Rgb> {
+    let colour = |i: usize| palette.get(i).copied().unwrap_or(COLLIDER_PALETTE[i]);
+    // Random weights only go up to 99, so anything heavier is the full
+    // colour, and anything lighter than a quarter of it is too hard to see
+    let shade = |i: usize, w: &W| colour(i).shade(0.25 + 0.75 * w.to_f64() / 99.0);
+    state
+        .cells()
+        .iter()
+        .map(|c| match c {
+            Cell::Empty => colour(0),
+            Cell::Stationary(w) => shade(1, w),
+            Cell::InMotion(Moving::Left(w)) => shade(2, w),
+            Cell::InMotion(Moving::Right(w)) => shade(3, w),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    /// Colour netpbm
+    Ppm,
+    /// Black and white netpbm, anything dark is black
+    Pbm,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Format, String> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("png") => Ok(Format::Png),
+            Some("ppm") => Ok(Format::Ppm),
+            Some("pbm") => Ok(Format::Pbm),
+            _ => Err(format!(
+                "Can't tell what kind of image '{}' should be, use .png, .ppm or .pbm",
+                path.display()
+            )),
+        }
+    }
+}
+
+/**
+ * A space-time diagram, one row of pixels per generation
+ *
+ * Rows narrower than the image are padded on the right with `background`,
+ * so anything that needs lining up has to be padded before it's pushed.
+ */
+pub struct Diagram {
+    width: usize,
+    background: Rgb,
+    rows: Vec<Vec<Rgb>>,
+}
+
+impl Diagram {
+    pub fn new(background: Rgb) -> Diagram {
+        Diagram {
+            width: 0,
+            background,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn background(&self) -> Rgb {
+        self.background
+    }
+
+    pub fn push(&mut self, row: Vec<Rgb>) {
+        self.width = self.width.max(row.len());
+        self.rows.push(row);
+    }
+
+    fn pixels(&self, cell_size: usize) -> impl Iterator<Item = Vec<Rgb>> + '_ {
+        self.rows.iter().flat_map(move |row| {
+            let scaled: Vec<Rgb> = (0..self.width)
+                .map(|x| row.get(x).copied().unwrap_or(self.background))
+                .flat_map(|pixel| std::iter::repeat_n(pixel, cell_size))
+                .collect();
+            std::iter::repeat_n(scaled, cell_size)
+        })
+    }
+
+    pub fn write<W: Write>(
+        &self,
+        out: W,
+        format: Format,
+        cell_size: usize,
+    ) -> Result<(), Box<dyn Error>> {
+        let width = self.width * cell_size;
+        let height = self.rows.len() * cell_size;
+        match format {
+            Format::Png => {
+                let mut encoder = png::Encoder::new(out, width.try_into()?, height.try_into()?);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                let data: Vec<u8> = self
+                    .pixels(cell_size)
+                    .flatten()
+                    .flat_map(|Rgb(r, g, b)| [r, g, b])
+                    .collect();
+                encoder.write_header()?.write_image_data(&data)?;
+            }
+            Format::Ppm => {
+                let mut out = out;
+                write!(out, "P6\n{width} {height}\n255\n")?;
+                for row in self.pixels(cell_size) {
+                    let bytes: Vec<u8> = row.iter().flat_map(|Rgb(r, g, b)| [*r, *g, *b]).collect();
+                    out.write_all(&bytes)?;
+                }
+            }
+            Format::Pbm => {
+                let mut out = out;
+                write!(out, "P4\n{width} {height}\n")?;
+                for row in self.pixels(cell_size) {
+                    // Eight pixels to a byte, most significant bit first, and
+                    // each row starts on a fresh byte
+                    let bytes: Vec<u8> = row
+                        .chunks(8)
+                        .map(|pixels| {
+                            pixels.iter().enumerate().fold(0u8, |acc, (i, p)| {
+                                if p.is_dark() { acc | (0x80 >> i) } else { acc }
+                            })
+                        })
+                        .collect();
+                    out.write_all(&bytes)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path, cell_size: usize) -> Result<(), Box<dyn Error>> {
+        let format = Format::from_path(path)?;
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(&mut out, format, cell_size)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagram() -> Diagram {
+        let mut diagram = Diagram::new(Rgb::WHITE);
+        diagram.push(vec![Rgb::BLACK, Rgb::WHITE, Rgb(1, 2, 3)]);
+        diagram.push(vec![Rgb::WHITE]);
+        diagram
+    }
+
+    #[test]
+    fn parses_colours() {
+        assert_eq!("#ff8800".parse(), Ok(Rgb(0xff, 0x88, 0x00)));
+        assert_eq!("0a0B0c".parse(), Ok(Rgb(0x0a, 0x0b, 0x0c)));
+        assert!("#ff88".parse::<Rgb>().is_err());
+        assert!("#gg8800".parse::<Rgb>().is_err());
+    }
+
+    #[test]
+    fn shades_towards_white() {
+        assert_eq!(Rgb(0, 100, 255).shade(1.0), Rgb(0, 100, 255));
+        assert_eq!(Rgb(0, 100, 255).shade(0.0), Rgb::WHITE);
+        assert_eq!(Rgb(55, 155, 255).shade(0.5), Rgb(155, 205, 255));
+    }
+
+    #[test]
+    fn writes_ppm() {
+        let mut out = Vec::new();
+        diagram().write(&mut out, Format::Ppm, 1).unwrap();
+        let mut expected = b"P6\n3 2\n255\n".to_vec();
+        expected.extend([0, 0, 0, 255, 255, 255, 1, 2, 3]);
+        expected.extend([255; 9]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn writes_scaled_pbm() {
+        let mut out = Vec::new();
+        diagram().write(&mut out, Format::Pbm, 2).unwrap();
+        let mut expected = b"P4\n6 4\n".to_vec();
+        expected.extend([0b1100_1100, 0b1100_1100, 0, 0]);
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn writes_png() {
+        let mut out = Vec::new();
+        diagram().write(&mut out, Format::Png, 3).unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(out));
+        let reader = decoder.read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (9, 6));
+    }
+
+    #[test]
+    fn colours_collider_cells() {
+        let state: collisions::State = collisions::State::from_string("_ 99 -99 +99 +0").unwrap();
+        let row = collider_row(&state, &[]);
+        assert_eq!(
+            row[..4],
+            [
+                Rgb::WHITE,
+                COLLIDER_PALETTE[1],
+                COLLIDER_PALETTE[2],
+                COLLIDER_PALETTE[3]
+            ]
+        );
+        assert_eq!(row[4], COLLIDER_PALETTE[3].shade(0.25));
+    }
+}
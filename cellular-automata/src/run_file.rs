@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{collisions, simple};
+
+/**
+ * Everything needed to run an automaton again from the start, JSON or TOML
+ * depending on the extension of the file
+ *
+ * The initial state is always written out in full, even when it was random,
+ * so a run doesn't depend on the random state generators staying the same.
+ * The seed is kept as well though, since the probabilistic physics needs it.
+ */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "automaton", rename_all = "kebab-case")]
+pub enum RunFile {
+    Simple(SimpleRun),
+    Collider(ColliderRun),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SimpleRun {
+    #[serde(with = "big_number")]
+    pub rule: u128,
+    pub radius: usize,
+    pub colours: u8,
+    pub neighbourhood: simple::Neighbourhood,
+    pub boundary: simple::Boundary,
+    /**
+     * In the same format as `--state`
+     */
+    pub state: String,
+    pub reversible: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    /**
+     * The seed the state was generated from, if it was random. Only there so
+     * it can be printed at the end like it was the first time.
+     */
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "big_number::optional"
+    )]
+    pub seed: Option<u64>,
+    pub generations: u32,
+    pub until_cycle: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ColliderRun {
+    pub physics: collisions::Physics,
+    pub bounce: bool,
+    pub damage: bool,
+    pub topology: collisions::Topology,
+    /**
+     * In the `DebugOutput` format, which keeps the speeds
+     */
+    pub state: String,
+    #[serde(with = "big_number")]
+    pub seed: u64,
+    pub generations: u32,
+}
+
+/**
+ * What kind of file a run is saved in, from its extension
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Format, String> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Ok(Format::Json),
+            Some("toml") => Ok(Format::Toml),
+            _ => Err(format!(
+                "Can't tell what kind of run file '{}' should be, use .json or .toml",
+                path.display()
+            )),
+        }
+    }
+}
+
+impl RunFile {
+    pub fn to_text(&self, format: Format) -> Result<String, Box<dyn Error>> {
+        Ok(match format {
+            Format::Json => serde_json::to_string_pretty(self)? + "\n",
+            Format::Toml => toml::to_string(self)?,
+        })
+    }
+
+    pub fn from_text(raw: &str, format: Format) -> Result<RunFile, Box<dyn Error>> {
+        Ok(match format {
+            Format::Json => serde_json::from_str(raw)?,
+            Format::Toml => toml::from_str(raw)?,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let raw = self.to_text(Format::from_path(path)?)?;
+        std::fs::write(path, raw)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<RunFile, Box<dyn Error>> {
+        let raw = std::fs::read_to_string(path)?;
+        RunFile::from_text(&raw, Format::from_path(path)?)
+            .map_err(|e| format!("Couldn't read run file '{}': {e}", path.display()).into())
+    }
+
+    pub fn into_simple(self) -> Result<SimpleRun, String> {
+        match self {
+            RunFile::Simple(run) => Ok(run),
+            RunFile::Collider(_) => Err("That's a collider run, not a simple one".to_string()),
+        }
+    }
+
+    pub fn into_collider(self) -> Result<ColliderRun, String> {
+        match self {
+            RunFile::Collider(run) => Ok(run),
+            RunFile::Simple(_) => Err("That's a simple run, not a collider one".to_string()),
+        }
+    }
+}
+
+/**
+ * TOML integers are 64-bit and signed, so seeds and rule codes too big for
+ * that are written as strings instead. Either is accepted when reading, in
+ * JSON as well.
+ */
+mod big_number {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Number(u64),
+        Text(String),
+    }
+
+    fn parse<'de, D: Deserializer<'de>, T: TryFrom<u128>>(raw: Raw) -> Result<T, D::Error> {
+        let n: u128 = match raw {
+            Raw::Number(n) => n.into(),
+            Raw::Text(text) => text.parse().map_err(D::Error::custom)?,
+        };
+        T::try_from(n).map_err(|_| D::Error::custom(format!("{n} is too big")))
+    }
+
+    pub fn serialize<S: Serializer, T: Copy + Into<u128>>(
+        n: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let n: u128 = (*n).into();
+        match i64::try_from(n) {
+            Ok(small) => serializer.serialize_i64(small),
+            Err(_) => serializer.serialize_str(&n.to_string()),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: TryFrom<u128>>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        parse::<D, T>(Raw::deserialize(deserializer)?)
+    }
+
+    pub mod optional {
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        use super::Raw;
+
+        pub fn serialize<S: Serializer, T: Copy + Into<u128>>(
+            n: &Option<T>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match n {
+                Some(n) => super::serialize(n, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, T: TryFrom<u128>>(
+            deserializer: D,
+        ) -> Result<Option<T>, D::Error> {
+            Option::<Raw>::deserialize(deserializer)?
+                .map(super::parse::<D, T>)
+                .transpose()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simple_run() -> SimpleRun {
+        SimpleRun {
+            rule: 110,
+            radius: 1,
+            colours: 2,
+            neighbourhood: simple::Neighbourhood::Full,
+            boundary: simple::Boundary::Periodic,
+            state: "  X X  ".to_string(),
+            reversible: false,
+            previous: None,
+            seed: None,
+            generations: 32,
+            until_cycle: true,
+        }
+    }
+
+    fn collider_run() -> ColliderRun {
+        ColliderRun {
+            physics: collisions::Physics::Probabilistic,
+            bounce: true,
+            damage: false,
+            topology: collisions::Topology::Walls,
+            state: "+3@2 _ 5 -1@1/3 ".to_string(),
+            seed: 9,
+            generations: 0,
+        }
+    }
+
+    fn round_trip(run: RunFile) {
+        for format in [Format::Json, Format::Toml] {
+            let raw = run.to_text(format).unwrap();
+            assert_eq!(RunFile::from_text(&raw, format).unwrap(), run, "{raw}");
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        round_trip(RunFile::Simple(simple_run()));
+        round_trip(RunFile::Collider(collider_run()));
+        round_trip(RunFile::Simple(SimpleRun {
+            rule: u128::MAX,
+            radius: 2,
+            colours: 3,
+            neighbourhood: simple::Neighbourhood::OuterTotalistic,
+            boundary: simple::Boundary::Growing,
+            reversible: true,
+            previous: Some(" 12 ".to_string()),
+            seed: Some(u64::MAX),
+            ..simple_run()
+        }));
+        round_trip(RunFile::Collider(ColliderRun {
+            seed: u64::MAX,
+            ..collider_run()
+        }));
+    }
+
+    #[test]
+    fn reads_what_people_write() {
+        let raw = r#"
+            automaton = "collider"
+            physics = "merge"
+            bounce = false
+            damage = false
+            topology = "ring"
+            state = "+3 _ -2"
+            seed = "18446744073709551615"
+            generations = 10
+        "#;
+        let run = RunFile::from_text(raw, Format::Toml)
+            .unwrap()
+            .into_collider()
+            .unwrap();
+        assert_eq!(run.physics, collisions::Physics::Merge);
+        assert_eq!(run.topology, collisions::Topology::Ring);
+        assert_eq!(run.seed, u64::MAX);
+
+        // Seeds and previous rows can be left out of simple runs
+        let raw = r#"{
+            "automaton": "simple", "rule": 30, "radius": 1, "colours": 2,
+            "neighbourhood": "full", "boundary": "dead", "state": "X",
+            "reversible": false, "generations": 4, "until-cycle": false
+        }"#;
+        let run = RunFile::from_text(raw, Format::Json).unwrap();
+        assert!(run.clone().into_collider().is_err());
+        assert_eq!(run.into_simple().unwrap().seed, None);
+
+        let raw = r#"{"automaton": "collider", "seed": "-1"}"#;
+        assert!(RunFile::from_text(raw, Format::Json).is_err());
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(Format::from_path(Path::new("run.json")), Ok(Format::Json));
+        assert_eq!(Format::from_path(Path::new("a/run.toml")), Ok(Format::Toml));
+        assert!(Format::from_path(Path::new("run.yaml")).is_err());
+        assert!(Format::from_path(Path::new("run")).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+/**
+ * Picks which generations of a run get written out, for runs too long to want
+ * all of them
+ *
+ * Every `every`th generation is written, starting with the first, or with
+ * `only_final` none of them are. The last generation is always written either
+ * way, so the output shows where the run ended up.
+ */
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    every: u64,
+    only_final: bool,
+    generation: u64,
+}
+
+impl Sampler {
+    pub fn new(every: u64, only_final: bool) -> Sampler {
+        Sampler {
+            every: every.max(1),
+            only_final,
+            generation: 0,
+        }
+    }
+
+    /**
+     * Whether to write the next generation, where `last` is whether there
+     * won't be any more after it
+     */
+    pub fn take(&mut self, last: bool) -> bool {
+        let generation = self.generation;
+        self.generation += 1;
+        last || (!self.only_final && generation.is_multiple_of(self.every))
+    }
+
+    /**
+     * Just the rows that should be written. This has to look a row ahead to
+     * know which one is the last.
+     */
+    pub fn sample<I: Iterator>(mut self, rows: I) -> impl Iterator<Item = I::Item> {
+        let mut rows = rows.peekable();
+        std::iter::from_fn(move || {
+            loop {
+                let row = rows.next()?;
+                if self.take(rows.peek().is_none()) {
+                    return Some(row);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampled(every: u64, only_final: bool, rows: u32) -> Vec<u32> {
+        Sampler::new(every, only_final).sample(0..rows).collect()
+    }
+
+    #[test]
+    fn samples_every_nth() {
+        assert_eq!(sampled(1, false, 4), [0, 1, 2, 3]);
+        assert_eq!(sampled(3, false, 10), [0, 3, 6, 9]);
+        // The last one is always there
+        assert_eq!(sampled(4, false, 10), [0, 4, 8, 9]);
+        assert_eq!(sampled(100, false, 10), [0, 9]);
+        assert_eq!(sampled(3, false, 0), [0u32; 0]);
+    }
+
+    #[test]
+    fn only_final() {
+        assert_eq!(sampled(1, true, 10), [9]);
+        assert_eq!(sampled(1, true, 1), [0]);
+        assert_eq!(sampled(1, true, 0), [0u32; 0]);
+    }
+}
@@ -1,4 +1,27 @@
+mod packed;
+mod predecessors;
+
+use std::collections::HashMap;
 use std::fmt::{Display, Write};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub use packed::Packed;
+
+/**
+ * Anything that can work out the next colour of a cell from its neighbourhood
+ *
+ * Cells are colours in `0..colours()`, and the neighbourhood handed to `apply`
+ * is always `2 * radius() + 1` cells wide, with the cell being updated in the
+ * middle.
+ */
+pub trait Rule {
+    fn radius(&self) -> usize;
+    fn colours(&self) -> u8;
+    fn apply(&self, neighbourhood: &[u8]) -> u8;
+}
 
 #[derive(Debug)]
 pub struct Wolfram {
@@ -21,74 +44,915 @@ impl Wolfram {
         Wolfram { lookup }
     }
 
-    pub fn merge(&self, left: &bool, middle: &bool, right: &bool) -> bool {
+    pub fn code(&self) -> u8 {
+        self.lookup
+            .iter()
+            .rev()
+            .fold(0, |acc, is_set| (acc << 1) | u8::from(*is_set))
+    }
+
+    /**
+     * The same rule with left and right swapped
+     */
+    pub fn mirror(&self) -> Wolfram {
+        let lookup = (0..8)
+            .map(|i| {
+                let swapped = ((i & 0x1) << 2) | (i & 0x2) | ((i & 0x4) >> 2);
+                self.lookup[swapped]
+            })
+            .collect();
+        Wolfram { lookup }
+    }
+
+    /**
+     * The same rule with live and dead cells swapped
+     */
+    pub fn complement(&self) -> Wolfram {
+        let lookup = (0..8).map(|i| !self.lookup[7 - i]).collect();
+        Wolfram { lookup }
+    }
+
+    /**
+     * Both of the above, which is the same whichever order they're done in
+     */
+    pub fn mirror_complement(&self) -> Wolfram {
+        self.mirror().complement()
+    }
+
+    /**
+     * The rule and the ones that only differ from it by swapping left and
+     * right or live and dead, smallest code first and without duplicates.
+     * These all behave the same way, just flipped.
+     */
+    pub fn equivalents(&self) -> Vec<Wolfram> {
+        let mut codes = vec![
+            self.code(),
+            self.mirror().code(),
+            self.complement().code(),
+            self.mirror_complement().code(),
+        ];
+        codes.sort_unstable();
+        codes.dedup();
+        codes.into_iter().map(Wolfram::from_code).collect()
+    }
+
+    /**
+     * The equivalent rule with the smallest code, there are 88 different ones
+     */
+    pub fn canonical(&self) -> Wolfram {
+        // Never empty, since the rule itself is always in there
+        self.equivalents().swap_remove(0)
+    }
+}
+impl Rule for Wolfram {
+    fn radius(&self) -> usize {
+        1
+    }
+
+    fn colours(&self) -> u8 {
+        2
+    }
+
+    fn apply(&self, neighbourhood: &[u8]) -> u8 {
         // Couldn't think of a better way to conditionally mask off the bits
         // to create the index I wanted
-        let l_mask: usize = if *left { 0x4 } else { 0x0 };
-        let m_mask: usize = if *middle { 0x2 } else { 0x0 };
-        let r_mask: usize = if *right { 0x1 } else { 0x0 };
-        *self.lookup.get(l_mask | m_mask | r_mask).unwrap_or(&false)
+        let l_mask: usize = if neighbourhood[0] != 0 { 0x4 } else { 0x0 };
+        let m_mask: usize = if neighbourhood[1] != 0 { 0x2 } else { 0x0 };
+        let r_mask: usize = if neighbourhood[2] != 0 { 0x1 } else { 0x0 };
+        (*self.lookup.get(l_mask | m_mask | r_mask).unwrap_or(&false)).into()
+    }
+}
+
+/**
+ * How a `Code` turns a neighbourhood into an index into its lookup table
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Neighbourhood {
+    /// Every arrangement of colours gets its own entry, like `Wolfram`
+    Full,
+    /// Only the sum of the colours in the neighbourhood matters
+    Totalistic,
+    /// The centre cell matters, but only the sum of the cells around it
+    OuterTotalistic,
+}
+
+/**
+ * Anything bigger than this is almost certainly a typo, and would take a
+ * silly amount of memory to build a lookup table for
+ */
+const MAX_LOOKUP_SIZE: usize = 1 << 20;
+
+/**
+ * A generalisation of `Wolfram` to `colours` colours and neighbourhoods
+ * `radius` cells to each side of the centre.
+ *
+ * The code is read as a number in base `colours`, where the digit for index
+ * `i` is the colour for neighbourhoods that land on index `i` in the lookup
+ * table, which mirrors how the elementary codes work. See
+ * https://mathworld.wolfram.com/TotalisticCellularAutomaton.html for the
+ * totalistic numbering.
+ */
+#[derive(Debug)]
+pub struct Code {
+    radius: usize,
+    colours: u8,
+    neighbourhood: Neighbourhood,
+    lookup: Vec<u8>,
+}
+impl Code {
+    pub fn new(
+        code: u128,
+        radius: usize,
+        colours: u8,
+        neighbourhood: Neighbourhood,
+    ) -> Result<Code, String> {
+        if colours < 2 {
+            return Err(format!("Need at least two colours, got {colours}"));
+        }
+        let width = 2 * radius + 1;
+        let max_colour = usize::from(colours - 1);
+        let size = match neighbourhood {
+            Neighbourhood::Full => u32::try_from(width)
+                .ok()
+                .and_then(|w| usize::from(colours).checked_pow(w)),
+            Neighbourhood::Totalistic => Some(width * max_colour + 1),
+            Neighbourhood::OuterTotalistic => {
+                Some(usize::from(colours) * ((width - 1) * max_colour + 1))
+            }
+        }
+        .filter(|size| *size <= MAX_LOOKUP_SIZE)
+        .ok_or_else(|| {
+            format!("A rule with radius {radius} and {colours} colours has too many neighbourhoods")
+        })?;
+
+        let mut remaining = code;
+        let lookup: Vec<u8> = (0..size)
+            .map(|_| {
+                let digit = remaining % u128::from(colours);
+                remaining /= u128::from(colours);
+                // Can't be bigger than `colours`, so this is fine
+                digit as u8
+            })
+            .collect();
+        if remaining != 0 {
+            return Err(format!(
+                "Code {code} is too large for a rule with {size} neighbourhoods and {colours} colours"
+            ));
+        }
+
+        Ok(Code {
+            radius,
+            colours,
+            neighbourhood,
+            lookup,
+        })
+    }
+
+    fn index(&self, neighbourhood: &[u8]) -> usize {
+        let sum = || neighbourhood.iter().map(|c| usize::from(*c)).sum::<usize>();
+        match self.neighbourhood {
+            Neighbourhood::Full => neighbourhood.iter().fold(0, |acc, c| {
+                acc * usize::from(self.colours) + usize::from(*c)
+            }),
+            Neighbourhood::Totalistic => sum(),
+            Neighbourhood::OuterTotalistic => {
+                let centre = usize::from(neighbourhood[self.radius]);
+                centre + usize::from(self.colours) * (sum() - centre)
+            }
+        }
     }
 }
+impl Rule for Code {
+    fn radius(&self) -> usize {
+        self.radius
+    }
+
+    fn colours(&self) -> u8 {
+        self.colours
+    }
 
+    fn apply(&self, neighbourhood: &[u8]) -> u8 {
+        *self.lookup.get(self.index(neighbourhood)).unwrap_or(&0)
+    }
+}
+
+/**
+ * What the cells past either end of the row look like
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Boundary {
+    /// The row wraps around, so the ends are neighbours
+    Periodic,
+    /// Everything past the ends is colour 0
+    Dead,
+    /// Everything past the ends is colour 1
+    Alive,
+    /// The ends act like mirrors
+    Reflective,
+    /// Like `Dead`, but the row is extended whenever a live cell gets close
+    /// enough to an end to affect what's past it
+    Growing,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct State {
-    cells: Vec<bool>,
+    cells: Vec<u8>,
+    // Where the first cell is relative to the first cell of the initial state,
+    // which only changes when the row grows to the left
+    origin: isize,
 }
 
 impl State {
-    pub fn new(cells: &[bool]) -> State {
+    pub fn new(cells: &[u8]) -> State {
         if cells.len() < 3 {
             let mut cells = cells.to_vec();
-            let mut padding = vec![false, false, false];
+            let mut padding = vec![0, 0, 0];
             cells.append(&mut padding);
-            let cells: Vec<bool> = cells.into_iter().take(3).collect();
-            State { cells }
+            let cells: Vec<u8> = cells.into_iter().take(3).collect();
+            State { cells, origin: 0 }
         } else {
             State {
                 cells: cells.to_vec(),
+                origin: 0,
             }
         }
     }
 
     /**
-     * Whitespace is 'off' and anything else is 'off'
+     * Whitespace is colour 0, digits are that colour, and anything else is colour 1
      */
     pub fn from_string(raw: &str) -> State {
-        let cells: Vec<bool> = raw.chars().map(|c| !c.is_whitespace()).collect();
+        let cells: Vec<u8> = raw
+            .chars()
+            .map(|c| match c.to_digit(10) {
+                // Single digit, so it'll always fit
+                Some(d) => d as u8,
+                None if c.is_whitespace() => 0,
+                None => 1,
+            })
+            .collect();
         State::new(&cells)
     }
 
     /**
-     * Calculates the next state
+     * A row of `width` cells where each one is alive with a chance of
+     * `density`, and live cells get a colour from 1 up to `colours - 1`
+     */
+    pub fn random<R: Rng>(width: usize, colours: u8, density: f64, rng: &mut R) -> State {
+        let cells: Vec<u8> = (0..width)
+            .map(|_| {
+                if rng.gen_bool(density) {
+                    rng.gen_range(1..colours.max(2))
+                } else {
+                    0
+                }
+            })
+            .collect();
+        State::new(&cells)
+    }
+
+    pub fn cells(&self) -> &[u8] {
+        &self.cells
+    }
+
+    /**
+     * The highest colour in the state, handy for checking it against a rule
+     */
+    pub fn max_colour(&self) -> u8 {
+        self.cells.iter().copied().max().unwrap_or(0)
+    }
+
+    /**
+     * Where the first cell is, relative to the first cell of the initial state.
+     *
+     * This is only ever non-zero for `Boundary::Growing`, and is needed to line
+     * up the rows when printing them.
+     */
+    pub fn origin(&self) -> isize {
+        self.origin
+    }
+
+    /**
+     * Adds `radius` dead cells to any end with a live cell that could affect
+     * what's past it
+     */
+    fn grow(&self, radius: usize) -> State {
+        let len = self.cells.len();
+        let grow_left = self.cells.iter().take(radius).any(|c| *c != 0);
+        let grow_right = self
+            .cells
+            .iter()
+            .skip(len.saturating_sub(radius))
+            .any(|c| *c != 0);
+        let mut cells = Vec::with_capacity(len + 2 * radius);
+        if grow_left {
+            cells.resize(radius, 0);
+        }
+        cells.extend_from_slice(&self.cells);
+        if grow_right {
+            cells.resize(cells.len() + radius, 0);
+        }
+        State {
+            cells,
+            origin: if grow_left {
+                self.origin - radius as isize
+            } else {
+                self.origin
+            },
+        }
+    }
+
+    /**
+     * The colour of a cell, where `index` is allowed to run off either end
      */
-    pub fn next(&self, rule: &Wolfram) -> State {
-        // We're using cycle to basically prepend the last cell to the iterator
-        // There's probably a better way to do this.
-        let mut prev_itr = self.cells.iter().cycle();
-        prev_itr.nth(self.cells.len() - 2);
+    fn get(&self, index: isize, boundary: Boundary) -> u8 {
+        let len = self.cells.len() as isize;
+        if (0..len).contains(&index) {
+            return self.cells[index as usize];
+        }
+        match boundary {
+            Boundary::Periodic => self.cells[index.rem_euclid(len) as usize],
+            Boundary::Dead | Boundary::Growing => 0,
+            Boundary::Alive => 1,
+            Boundary::Reflective => {
+                // Bouncing back and forth between the mirrors repeats every
+                // two lengths, and the second half is the row backwards
+                let folded = index.rem_euclid(2 * len);
+                if folded < len {
+                    self.cells[folded as usize]
+                } else {
+                    self.cells[(2 * len - 1 - folded) as usize]
+                }
+            }
+        }
+    }
 
-        // Cycling this as well to append the first cell to the end
-        // Again, probably a better way to do this.
-        let mut next_itr = self.cells.iter().cycle();
-        next_itr.next();
+    /**
+     * Calculates the next state
+     */
+    pub fn next(&self, rule: &dyn Rule, boundary: Boundary) -> State {
+        let grown;
+        let state = if boundary == Boundary::Growing {
+            grown = self.grow(rule.radius());
+            &grown
+        } else {
+            self
+        };
 
-        let curr_itr = self.cells.iter();
+        // Pad both ends out with whatever the boundary says is there, so
+        // every cell gets a full neighbourhood.
+        let radius = rule.radius() as isize;
+        let padded: Vec<u8> = (-radius..state.cells.len() as isize + radius)
+            .map(|i| state.get(i, boundary))
+            .collect();
 
-        let cells = prev_itr
-            .zip(curr_itr)
-            .zip(next_itr)
-            .map(|((p, c), n)| rule.merge(p, c, n))
+        let cells = padded
+            .windows(2 * rule.radius() + 1)
+            .map(|neighbourhood| rule.apply(neighbourhood))
             .collect();
 
-        State { cells }
+        State {
+            cells,
+            origin: state.origin,
+        }
     }
 }
 
 impl Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for c in &self.cells {
-            f.write_char(if *c { 'X' } else { ' ' })?;
+            f.write_char(match c {
+                0 => ' ',
+                1 => 'X',
+                _ => char::from_digit((*c).into(), 36).unwrap_or('?'),
+            })?;
         }
         Ok(())
     }
 }
+
+/**
+ * A second-order automaton, where each row depends on the two before it
+ *
+ * The next row is the rule applied to the current row, minus the previous
+ * row (mod the number of colours, so it's an XOR for two colours). That means
+ * the previous row can always be worked out again from the next one with the
+ * same formula, so every rule becomes reversible.
+ *
+ * Both rows have to stay the same width, so `Boundary::Growing` isn't allowed.
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Reversible {
+    previous: State,
+    current: State,
+    boundary: Boundary,
+}
+
+impl Reversible {
+    pub fn new(previous: State, current: State, boundary: Boundary) -> Result<Reversible, String> {
+        if boundary == Boundary::Growing {
+            return Err("Reversible rules can't use a growing boundary".to_string());
+        }
+        if previous.cells.len() != current.cells.len() {
+            return Err(format!(
+                "The previous row has {} cells, but the current one has {}",
+                previous.cells.len(),
+                current.cells.len()
+            ));
+        }
+        Ok(Reversible {
+            previous,
+            current,
+            boundary,
+        })
+    }
+
+    pub fn previous(&self) -> &State {
+        &self.previous
+    }
+
+    pub fn current(&self) -> &State {
+        &self.current
+    }
+
+    /**
+     * The rule applied to `row`, minus `other`
+     */
+    fn step(&self, rule: &dyn Rule, row: &State, other: &State) -> State {
+        let colours = rule.colours();
+        let cells = row
+            .next(rule, self.boundary)
+            .cells
+            .iter()
+            .zip(&other.cells)
+            // In `u16` since `a + colours` can be more than 255
+            .map(|(a, b)| {
+                let (a, b, colours) = (u16::from(*a), u16::from(*b), u16::from(colours));
+                ((a + colours - b % colours) % colours) as u8
+            })
+            .collect();
+        State {
+            cells,
+            origin: row.origin,
+        }
+    }
+
+    pub fn forward(&self, rule: &dyn Rule) -> Reversible {
+        Reversible {
+            previous: self.current.clone(),
+            current: self.step(rule, &self.current, &self.previous),
+            boundary: self.boundary,
+        }
+    }
+
+    /**
+     * Undoes `forward`, since the row before `previous` is the rule applied to
+     * `previous`, minus `current`
+     */
+    pub fn backward(&self, rule: &dyn Rule) -> Reversible {
+        Reversible {
+            previous: self.step(rule, &self.previous, &self.current),
+            current: self.previous.clone(),
+            boundary: self.boundary,
+        }
+    }
+}
+
+/**
+ * How a run settled into repeating itself
+ *
+ * `transient` is the generation the cycle first started on, and `period` is
+ * how many generations it takes to come back around. A fixed point is a
+ * cycle with a period of 1.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub transient: usize,
+    pub period: usize,
+}
+
+/**
+ * Spots the first time a run repeats a state
+ *
+ * Only a hash of each state is kept, so memory stays reasonable on long runs.
+ * That does mean two different states with the same hash would be mistaken
+ * for a repeat, but with 64 bit hashes that's not worth worrying about.
+ */
+#[derive(Debug, Default)]
+pub struct CycleDetector {
+    seen: HashMap<u64, usize>,
+    generation: usize,
+}
+
+impl CycleDetector {
+    pub fn new() -> CycleDetector {
+        CycleDetector::default()
+    }
+
+    /**
+     * Feed this every state in order, it returns the cycle once one of them
+     * is a repeat of an earlier one
+     */
+    pub fn observe<S: Hash>(&mut self, state: &S) -> Option<Cycle> {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        let generation = self.generation;
+        self.generation += 1;
+        match self.seen.get(&hasher.finish()) {
+            Some(first) => Some(Cycle {
+                transient: *first,
+                period: generation - first,
+            }),
+            None => {
+                self.seen.insert(hasher.finish(), generation);
+                None
+            }
+        }
+    }
+}
+
+/**
+ * How many generations a run goes for. With `until_cycle`, 0 `generations`
+ * means until it repeats, however long that takes.
+ *
+ * That isn't allowed with `Boundary::Growing`, since a row that keeps getting
+ * wider never repeats, and all the rows have to be kept to line them up once
+ * the run's over, so it would just use more and more memory.
+ */
+pub fn generation_limit(
+    boundary: Boundary,
+    generations: usize,
+    until_cycle: bool,
+) -> Result<usize, String> {
+    match (until_cycle, generations) {
+        (true, 0) if boundary == Boundary::Growing => Err(
+            "--until-cycle needs a number of --generations with a growing boundary, a growing row might never repeat"
+                .to_string(),
+        ),
+        (true, 0) => Ok(usize::MAX),
+        _ => Ok(generations),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn run(rule: &dyn Rule, initial: &str, generations: usize) -> Vec<String> {
+        run_with(rule, Boundary::Periodic, initial, generations)
+    }
+
+    fn run_with(
+        rule: &dyn Rule,
+        boundary: Boundary,
+        initial: &str,
+        generations: usize,
+    ) -> Vec<String> {
+        let mut state = State::from_string(initial);
+        let mut rows = Vec::new();
+        for _ in 0..generations {
+            rows.push(state.to_string());
+            state = state.next(rule, boundary);
+        }
+        rows
+    }
+
+    #[test]
+    fn wolfram_codes_round_trip() {
+        for code in 0..=255u8 {
+            assert_eq!(Wolfram::from_code(code).code(), code);
+        }
+    }
+
+    #[test]
+    fn mirror_and_complement() {
+        // Rule 30 and its friends are the textbook example
+        assert_eq!(Wolfram::from_code(30).mirror().code(), 86);
+        assert_eq!(Wolfram::from_code(30).complement().code(), 135);
+        assert_eq!(Wolfram::from_code(30).mirror().complement().code(), 149);
+        assert_eq!(Wolfram::from_code(110).mirror().code(), 124);
+        assert_eq!(Wolfram::from_code(30).mirror_complement().code(), 149);
+        // Rule 90 is its own mirror image
+        assert_eq!(Wolfram::from_code(90).mirror().code(), 90);
+        assert_eq!(Wolfram::from_code(90).complement().code(), 165);
+    }
+
+    #[test]
+    fn reversible_runs_backwards() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let rules: Vec<Box<dyn Rule>> = vec![
+            Box::new(Wolfram::from_code(30)),
+            Box::new(Wolfram::from_code(110)),
+            Box::new(Code::new(1599, 1, 3, Neighbourhood::Totalistic).unwrap()),
+            Box::new(Code::new(0xdeadbeef, 2, 2, Neighbourhood::Full).unwrap()),
+        ];
+        for rule in rules {
+            for boundary in [Boundary::Periodic, Boundary::Dead, Boundary::Reflective] {
+                let mut random = || {
+                    let cells: Vec<u8> =
+                        (0..40).map(|_| rng.gen_range(0..rule.colours())).collect();
+                    State::new(&cells)
+                };
+                let start = Reversible::new(random(), random(), boundary).unwrap();
+                let mut run = start.clone();
+                for _ in 0..50 {
+                    run = run.forward(rule.as_ref());
+                }
+                assert_ne!(run, start);
+                for _ in 0..50 {
+                    run = run.backward(rule.as_ref());
+                }
+                assert_eq!(run, start, "{boundary:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn reversible_with_lots_of_colours() {
+        // Every cell turns into colour 199, which is more than a `u8` can
+        // take away from once the number of colours is added
+        let rule = Code::new(199, 0, 200, Neighbourhood::Totalistic).unwrap();
+        let start = Reversible::new(
+            State::new(&[1, 0, 150]),
+            State::new(&[0, 0, 0]),
+            Boundary::Periodic,
+        )
+        .unwrap();
+        let next = start.forward(&rule);
+        assert_eq!(next.current().cells, [198, 199, 49]);
+        assert_eq!(next.backward(&rule), start);
+    }
+
+    #[test]
+    fn reversible_xors_the_previous_row() {
+        let rule = Wolfram::from_code(90);
+        let previous = State::from_string("X   X");
+        let current = State::from_string("  X  ");
+        let next = Reversible::new(previous, current, Boundary::Dead)
+            .unwrap()
+            .forward(&rule);
+        // Rule 90 alone would give " X X "
+        assert_eq!(next.current().to_string(), "XX XX");
+        assert_eq!(next.previous().to_string(), "  X  ");
+        assert!(
+            Reversible::new(
+                State::from_string("   "),
+                State::from_string("    "),
+                Boundary::Dead
+            )
+            .is_err()
+        );
+        assert!(
+            Reversible::new(
+                State::from_string("   "),
+                State::from_string("   "),
+                Boundary::Growing
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn random_states_follow_the_seed() {
+        let random = |seed| State::random(200, 4, 0.3, &mut StdRng::seed_from_u64(seed));
+        assert_eq!(random(7), random(7));
+        assert_ne!(random(7), random(8));
+
+        let state = random(7);
+        assert_eq!(state.cells().len(), 200);
+        assert!(state.max_colour() <= 3);
+        let alive = state.cells().iter().filter(|c| **c != 0).count();
+        assert!((30..90).contains(&alive), "{alive} live cells");
+
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_eq!(State::random(50, 2, 0.0, &mut rng).max_colour(), 0);
+        assert!(
+            State::random(50, 2, 1.0, &mut rng)
+                .cells()
+                .iter()
+                .all(|c| *c == 1)
+        );
+    }
+
+    #[test]
+    fn equivalence_classes() {
+        let codes = |code: u8| -> Vec<u8> {
+            Wolfram::from_code(code)
+                .equivalents()
+                .iter()
+                .map(|r| r.code())
+                .collect()
+        };
+        assert_eq!(codes(30), [30, 86, 135, 149]);
+        assert_eq!(codes(110), [110, 124, 137, 193]);
+        assert_eq!(codes(90), [90, 165]);
+        assert_eq!(codes(150), [150]);
+        assert_eq!(Wolfram::from_code(149).canonical().code(), 30);
+        assert_eq!(Wolfram::from_code(193).canonical().code(), 110);
+
+        let mut canonical: Vec<u8> = (0..=255)
+            .map(|code| Wolfram::from_code(code).canonical().code())
+            .collect();
+        canonical.sort_unstable();
+        canonical.dedup();
+        assert_eq!(canonical.len(), 88);
+    }
+
+    #[test]
+    fn full_codes_match_wolfram() {
+        let initial = "X  XX X   XXX X XX  X";
+        for code in 0..=255u8 {
+            let general = Code::new(code.into(), 1, 2, Neighbourhood::Full).unwrap();
+            assert_eq!(
+                run(&general, initial, 10),
+                run(&Wolfram::from_code(code), initial, 10),
+                "code {code}"
+            );
+        }
+    }
+
+    #[test]
+    fn totalistic_10_is_rule_150() {
+        let rule = Code::new(10, 1, 2, Neighbourhood::Totalistic).unwrap();
+        let initial = "          X          ";
+        assert_eq!(
+            run(&rule, initial, 12),
+            run(&Wolfram::from_code(150), initial, 12)
+        );
+    }
+
+    #[test]
+    fn outer_totalistic_38_is_rule_150() {
+        let rule = Code::new(38, 1, 2, Neighbourhood::OuterTotalistic).unwrap();
+        let initial = "  XX  X X   XXX X     ";
+        assert_eq!(
+            run(&rule, initial, 12),
+            run(&Wolfram::from_code(150), initial, 12)
+        );
+    }
+
+    #[test]
+    fn totalistic_1599() {
+        let rule = Code::new(1599, 1, 3, Neighbourhood::Totalistic).unwrap();
+        assert_eq!(
+            run(&rule, "       1       ", 4),
+            vec![
+                "       X       ",
+                "      222      ",
+                "      X2X      ",
+                "     22X22     ",
+            ]
+        );
+    }
+
+    #[test]
+    fn larger_radius() {
+        // Radius 2 totalistic, alive if exactly one cell in the window is alive
+        let rule = Code::new(0b10, 2, 2, Neighbourhood::Totalistic).unwrap();
+        assert_eq!(
+            run(&rule, "      X      ", 3),
+            vec!["      X      ", "    XXXXX    ", "  X       X  ",]
+        );
+    }
+
+    #[test]
+    fn wraps_around() {
+        let rule = Code::new(0b10, 2, 2, Neighbourhood::Totalistic).unwrap();
+        assert_eq!(run(&rule, "X      ", 2), vec!["X      ", "XXX  XX"]);
+    }
+
+    #[test]
+    fn boundaries() {
+        let rule = Wolfram::from_code(90);
+        assert_eq!(
+            run_with(&rule, Boundary::Periodic, "X    ", 2),
+            vec!["X    ", " X  X"]
+        );
+        assert_eq!(
+            run_with(&rule, Boundary::Dead, "X    ", 2),
+            vec!["X    ", " X   "]
+        );
+        assert_eq!(
+            run_with(&rule, Boundary::Alive, "     ", 2),
+            vec!["     ", "X   X"]
+        );
+        assert_eq!(
+            run_with(&rule, Boundary::Reflective, "X    ", 2),
+            vec!["X    ", "XX   "]
+        );
+    }
+
+    #[test]
+    fn reflective_past_the_whole_row() {
+        // Radius 3 on a row of 3 has to reflect more than once
+        let rule = Code::new(0b10, 3, 2, Neighbourhood::Totalistic).unwrap();
+        let state = State::from_string("X  ").next(&rule, Boundary::Reflective);
+        // Reflected out to three cells each side the row looks like
+        // "  X|X  |  X", so the neighbourhoods have 2, 2 and 3 live cells
+        assert_eq!(state.to_string(), "   ");
+    }
+
+    #[test]
+    fn growing_runs_need_a_limit() {
+        // Shifts left forever, so the row keeps growing and never repeats
+        let rule = Wolfram::from_code(2);
+        let mut state = State::from_string("  X  ");
+        let mut detector = CycleDetector::new();
+        for _ in 0..200 {
+            assert_eq!(detector.observe(&state), None);
+            state = state.next(&rule, Boundary::Growing);
+        }
+        assert!(generation_limit(Boundary::Growing, 0, true).is_err());
+        assert_eq!(generation_limit(Boundary::Growing, 200, true), Ok(200));
+        assert_eq!(
+            generation_limit(Boundary::Periodic, 0, true),
+            Ok(usize::MAX)
+        );
+        assert_eq!(generation_limit(Boundary::Growing, 0, false), Ok(0));
+    }
+
+    #[test]
+    fn growing() {
+        let rule = Wolfram::from_code(90);
+        let state = State::from_string("X  ");
+        let state = state.next(&rule, Boundary::Growing);
+        assert_eq!(state.to_string(), "X X ");
+        assert_eq!(state.origin(), -1);
+        let state = state.next(&rule, Boundary::Growing);
+        assert_eq!(state.to_string(), "X   X");
+        assert_eq!(state.origin(), -2);
+        let state = state.next(&rule, Boundary::Growing);
+        assert_eq!(state.to_string(), "X X X X");
+        assert_eq!(state.origin(), -3);
+    }
+
+    fn find_cycle(rule: &dyn Rule, initial: &str, generations: usize) -> Option<Cycle> {
+        let mut detector = CycleDetector::new();
+        let mut state = State::from_string(initial);
+        for _ in 0..generations {
+            if let Some(cycle) = detector.observe(&state) {
+                return Some(cycle);
+            }
+            state = state.next(rule, Boundary::Periodic);
+        }
+        None
+    }
+
+    #[test]
+    fn finds_fixed_points() {
+        assert_eq!(
+            find_cycle(&Wolfram::from_code(0), "XX X  X", 10),
+            Some(Cycle {
+                transient: 1,
+                period: 1
+            })
+        );
+        assert_eq!(
+            find_cycle(&Wolfram::from_code(204), "XX X  X", 10),
+            Some(Cycle {
+                transient: 0,
+                period: 1
+            })
+        );
+    }
+
+    #[test]
+    fn finds_cycles() {
+        // Rule 170 just shifts everything one cell to the left
+        assert_eq!(
+            find_cycle(&Wolfram::from_code(170), "X    ", 10),
+            Some(Cycle {
+                transient: 0,
+                period: 5
+            })
+        );
+        // Rule 90 on a ring of 8 cells wipes out a single live cell
+        assert_eq!(
+            find_cycle(&Wolfram::from_code(90), "   X    ", 20),
+            Some(Cycle {
+                transient: 4,
+                period: 1
+            })
+        );
+        assert_eq!(
+            find_cycle(
+                &Wolfram::from_code(30),
+                &format!("{0}X{0}", " ".repeat(60)),
+                50
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_codes() {
+        assert!(Code::new(2187, 1, 3, Neighbourhood::Totalistic).is_err());
+        assert!(Code::new(2186, 1, 3, Neighbourhood::Totalistic).is_ok());
+        assert!(Code::new(0, 20, 2, Neighbourhood::Full).is_err());
+        assert!(Code::new(0, 1, 1, Neighbourhood::Full).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::fmt::{Display, Write};
+
+use super::{Boundary, Rule, State, Wolfram};
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+/**
+ * An elementary automaton state with 64 cells to a word
+ *
+ * Cell `i` is bit `i % 64` of word `i / 64`, and any bits past the last cell
+ * are always zero, which `next` relies on so it doesn't have to mask the
+ * neighbours it shifts in from the word after the last one.
+ *
+ * This only works for two colours and a radius of one, so it only takes
+ * `Wolfram` rules, but for those it gives exactly the same results as
+ * `State::next`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packed {
+    len: usize,
+    words: Vec<u64>,
+    origin: isize,
+}
+
+impl Packed {
+    /**
+     * Anything that isn't colour 0 is treated as alive
+     */
+    pub fn from_state(state: &State) -> Packed {
+        let mut words = vec![0u64; state.cells.len().div_ceil(WORD_BITS)];
+        for (i, c) in state.cells.iter().enumerate() {
+            if *c != 0 {
+                words[i / WORD_BITS] |= 1 << (i % WORD_BITS);
+            }
+        }
+        Packed {
+            len: state.cells.len(),
+            words,
+            origin: state.origin,
+        }
+    }
+
+    pub fn to_state(&self) -> State {
+        State {
+            cells: (0..self.len).map(|i| self.get(i).into()).collect(),
+            origin: self.origin,
+        }
+    }
+
+    pub fn origin(&self) -> isize {
+        self.origin
+    }
+
+    fn get(&self, index: usize) -> bool {
+        self.words[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+    }
+
+    fn set(&mut self, index: usize, alive: bool) {
+        let mask = 1 << (index % WORD_BITS);
+        if alive {
+            self.words[index / WORD_BITS] |= mask;
+        } else {
+            self.words[index / WORD_BITS] &= !mask;
+        }
+    }
+
+    /**
+     * Same as `State::grow`, adding a single dead cell to each end with a live
+     * cell on it
+     */
+    fn grow(&self) -> Packed {
+        let mut grown = self.clone();
+        if self.get(0) {
+            // Shifting everything up a bit, carrying the top bit of each word
+            // into the next one
+            let mut carry = 0;
+            for word in grown.words.iter_mut() {
+                let next_carry = *word >> (WORD_BITS - 1);
+                *word = (*word << 1) | carry;
+                carry = next_carry;
+            }
+            grown.len += 1;
+            grown.origin -= 1;
+            if grown.len > grown.words.len() * WORD_BITS {
+                grown.words.push(carry);
+            }
+        }
+        if grown.get(grown.len - 1) {
+            grown.len += 1;
+            if grown.len > grown.words.len() * WORD_BITS {
+                grown.words.push(0);
+            }
+        }
+        grown
+    }
+
+    /**
+     * The cells just past each end, using the same rules as `State::get`
+     */
+    fn ghosts(&self, boundary: Boundary) -> (bool, bool) {
+        match boundary {
+            Boundary::Periodic => (self.get(self.len - 1), self.get(0)),
+            Boundary::Dead | Boundary::Growing => (false, false),
+            Boundary::Alive => (true, true),
+            Boundary::Reflective => (self.get(0), self.get(self.len - 1)),
+        }
+    }
+
+    /**
+     * Calculates the next state, 64 cells at a time
+     */
+    pub fn next(&self, rule: &Wolfram, boundary: Boundary) -> Packed {
+        let grown;
+        let state = if boundary == Boundary::Growing {
+            grown = self.grow();
+            &grown
+        } else {
+            self
+        };
+
+        // The neighbourhoods this rule turns on, as (left, middle, right)
+        let code = rule.code();
+        let patterns: Vec<(bool, bool, bool)> = (0..8)
+            .filter(|p| code & (1 << p) != 0)
+            .map(|p| (p & 0x4 != 0, p & 0x2 != 0, p & 0x1 != 0))
+            .collect();
+        let pick = |want: bool, word: u64| if want { word } else { !word };
+
+        let words = &state.words;
+        let mut next: Vec<u64> = (0..words.len())
+            .map(|w| {
+                let middle = words[w];
+                // Bit i of `left` is the cell to the left of cell i, which is
+                // one bit lower, so the low bit comes from the top of the
+                // previous word. Same idea, mirrored, for `right`.
+                let before = if w > 0 { words[w - 1] } else { 0 };
+                let after = words.get(w + 1).copied().unwrap_or(0);
+                let left = (middle << 1) | (before >> (WORD_BITS - 1));
+                let right = (middle >> 1) | (after << (WORD_BITS - 1));
+                patterns.iter().fold(0, |acc, (l, m, r)| {
+                    acc | (pick(*l, left) & pick(*m, middle) & pick(*r, right))
+                })
+            })
+            .collect();
+
+        // Clear anything that ended up past the last cell
+        let used = state.len % WORD_BITS;
+        if let Some(last) = next.last_mut()
+            && used != 0
+        {
+            *last &= (1 << used) - 1;
+        }
+
+        let mut next = Packed {
+            len: state.len,
+            words: next,
+            origin: state.origin,
+        };
+
+        // The end cells were worked out as if there were dead cells past them,
+        // so they need redoing with whatever the boundary actually says.
+        let last = state.len - 1;
+        let (before, after) = state.ghosts(boundary);
+        let merge = |l: bool, m: bool, r: bool| rule.apply(&[l.into(), m.into(), r.into()]) != 0;
+        next.set(0, merge(before, state.get(0), state.get(1)));
+        next.set(last, merge(state.get(last - 1), state.get(last), after));
+
+        next
+    }
+}
+
+impl Display for Packed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for i in 0..self.len {
+            f.write_char(if self.get(i) { 'X' } else { ' ' })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const BOUNDARIES: [Boundary; 5] = [
+        Boundary::Periodic,
+        Boundary::Dead,
+        Boundary::Alive,
+        Boundary::Reflective,
+        Boundary::Growing,
+    ];
+
+    #[test]
+    fn round_trips() {
+        let state = State::from_string("X  XX X   XXX X XX  X");
+        assert_eq!(Packed::from_state(&state).to_state(), state);
+        assert_eq!(Packed::from_state(&state).to_string(), state.to_string());
+    }
+
+    #[test]
+    fn matches_state_next() {
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for code in 0..=255u8 {
+            let rule = Wolfram::from_code(code);
+            for len in [3, 5, 63, 64, 65, 127, 128, 129, 200] {
+                for boundary in BOUNDARIES {
+                    let cells: Vec<u8> = (0..len).map(|_| rng.gen_range(0..=1)).collect();
+                    let mut state = State::new(&cells);
+                    let mut packed = Packed::from_state(&state);
+                    for generation in 0..20 {
+                        assert_eq!(
+                            packed.to_state(),
+                            state,
+                            "rule {code}, {len} cells, {boundary:?}, generation {generation}"
+                        );
+                        state = state.next(&rule, boundary);
+                        packed = packed.next(&rule, boundary);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use super::{Rule, State, Wolfram};
+
+/**
+ * A node of the de Bruijn graph, which is a pair of neighbouring cells packed
+ * into two bits, left cell high
+ */
+type Node = usize;
+
+const NODES: Node = 4;
+
+/**
+ * Following the edge from `(a, b)` adds the cell `c`, which takes us to
+ * `(b, c)` and means the rule saw `(a, b, c)`
+ */
+fn edges(rule: &Wolfram, from: Node) -> impl Iterator<Item = (u8, Node)> + '_ {
+    let (a, b) = ((from >> 1) as u8, (from & 1) as u8);
+    (0..=1u8).map(move |c| {
+        (
+            rule.apply(&[a, b, c]),
+            (usize::from(b) << 1) | usize::from(c),
+        )
+    })
+}
+
+impl State {
+    /**
+     * Every row that turns into this one in a single generation of `rule`,
+     * with periodic boundaries
+     *
+     * A predecessor of `n` cells is a walk around the de Bruijn graph that
+     * reads out this row on its edges and ends up back where it started. For
+     * each place it could start, this first works backwards to find which
+     * nodes can still get home from each step, then only follows edges into
+     * those, so the search never hits a dead end and the work grows with the
+     * number of predecessors rather than with the `2^n` possible rows. There
+     * can still be a lot of them though, see `count_predecessors`.
+     */
+    pub fn predecessors(&self, rule: &Wolfram) -> Vec<State> {
+        let mut found = Vec::new();
+        for start in 0..NODES {
+            let paths = self.paths(rule, start);
+            if paths[0][start] == 0 {
+                continue;
+            }
+            // Each node is (cell i - 1, cell i), so the start node already
+            // has the last cell and the first one in it
+            self.walk(rule, &paths, start, &mut found);
+        }
+        found
+    }
+
+    /**
+     * How many predecessors there are, without listing them, which is handy
+     * since there can be up to `2^n` of them. Stops counting at `u128::MAX`.
+     */
+    pub fn count_predecessors(&self, rule: &Wolfram) -> u128 {
+        (0..NODES).fold(0, |total, start| {
+            total.saturating_add(self.paths(rule, start)[0][start])
+        })
+    }
+
+    /**
+     * An orphan, or Garden of Eden, has no predecessors at all
+     */
+    pub fn is_orphan(&self, rule: &Wolfram) -> bool {
+        self.count_predecessors(rule) == 0
+    }
+
+    /**
+     * `paths[i][node]` is how many walks from `node` at step `i` read the rest
+     * of the row and end on `end`
+     */
+    fn paths(&self, rule: &Wolfram, end: Node) -> Vec<[u128; NODES]> {
+        let len = self.cells.len();
+        let mut paths = vec![[0; NODES]; len + 1];
+        paths[len][end] = 1;
+        for i in (0..len).rev() {
+            for node in 0..NODES {
+                paths[i][node] = edges(rule, node)
+                    .filter(|(output, _)| *output == self.cells[i])
+                    .fold(0u128, |total, (_, to)| {
+                        total.saturating_add(paths[i + 1][to])
+                    });
+            }
+        }
+        paths
+    }
+
+    /**
+     * Every walk from `start` that reads out the row and gets back to
+     * `start`, following `paths` so it never goes anywhere it can't get back
+     * from
+     *
+     * This keeps its own stack rather than recursing, since it goes a cell
+     * deeper for every cell of the row, and rows can be long.
+     */
+    fn walk(&self, rule: &Wolfram, paths: &[[u128; NODES]], start: Node, found: &mut Vec<State>) {
+        // `tried[i]` is how many of the edges out of `path[i]` have been
+        // looked at so far
+        let mut path = vec![start];
+        let mut tried = vec![0];
+        while let Some(&from) = path.last() {
+            let step = path.len() - 1;
+            if step == self.cells.len() {
+                // The right hand cell of each node is the next cell of the
+                // row, and the last node is the start node again, so leave it
+                // off
+                let cells: Vec<u8> = path[..step].iter().map(|n| (n & 1) as u8).collect();
+                found.push(State { cells, origin: 0 });
+            }
+            let next = match step < self.cells.len() {
+                true => edges(rule, from).nth(tried[step]),
+                false => None,
+            };
+            match next {
+                Some((output, to)) => {
+                    tried[step] += 1;
+                    if output == self.cells[step] && paths[step + 1][to] != 0 {
+                        path.push(to);
+                        tried.push(0);
+                    }
+                }
+                None => {
+                    path.pop();
+                    tried.pop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple::Boundary;
+
+    fn brute_force(rule: &Wolfram, target: &State) -> Vec<State> {
+        let len = target.cells.len();
+        (0..1u32 << len)
+            .map(|bits| {
+                let cells: Vec<u8> = (0..len).map(|i| ((bits >> i) & 1) as u8).collect();
+                State::new(&cells)
+            })
+            .filter(|row| row.next(rule, Boundary::Periodic) == *target)
+            .collect()
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for code in 0..=255u8 {
+            let rule = Wolfram::from_code(code);
+            for target in ["   ", "X X", "XX  X", " XX X  ", "X  XXX X"] {
+                let target = State::from_string(target);
+                let mut found = target.predecessors(&rule);
+                let mut expected = brute_force(&rule, &target);
+                found.sort_by(|a, b| a.cells.cmp(&b.cells));
+                expected.sort_by(|a, b| a.cells.cmp(&b.cells));
+                assert_eq!(found, expected, "rule {code}, '{target}'");
+                assert_eq!(target.count_predecessors(&rule), expected.len() as u128);
+                assert_eq!(target.is_orphan(&rule), expected.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn lists_predecessors_of_long_rows() {
+        // Far deeper than the stack would go if the walk recursed for every
+        // cell. Rule 204 leaves every cell alone, so a row is its own only
+        // predecessor.
+        let cells: Vec<u8> = (0..200_000).map(|i| u8::from(i % 3 == 0)).collect();
+        let row = State::new(&cells);
+        assert_eq!(row.predecessors(&Wolfram::from_code(204)), [row]);
+    }
+
+    #[test]
+    fn counts_without_listing() {
+        // Everything turns into all dead cells under rule 0
+        let blank = State::new(&[0; 200]);
+        assert_eq!(blank.count_predecessors(&Wolfram::from_code(0)), u128::MAX);
+        assert_eq!(
+            State::new(&[0; 100]).count_predecessors(&Wolfram::from_code(0)),
+            1 << 100
+        );
+    }
+
+    #[test]
+    fn finds_orphans() {
+        // Under rule 90 a row of all live cells needs every other cell to
+        // alternate, which can't work all the way round an odd length
+        let rule = Wolfram::from_code(90);
+        assert!(State::from_string("XXXXX").is_orphan(&rule));
+        assert!(!State::from_string("XXXX").is_orphan(&rule));
+        assert_eq!(State::from_string("XXXX").predecessors(&rule).len(), 4);
+        // Or into anything with a colour the rule can't make
+        assert!(State::from_string("  2  ").is_orphan(&rule));
+    }
+}
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::simple::{Boundary, CycleDetector, State, Wolfram};
+
+/**
+ * What a rule did with one initial state
+ *
+ * `seed` is the position of the initial state in the list the survey was
+ * given, and `density` is the fraction of live cells in each generation.
+ * `class` is a guess at the Wolfram class, see `guess_class`.
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Metrics {
+    pub rule: u8,
+    pub seed: usize,
+    pub generations: usize,
+    pub density: Vec<f64>,
+    pub mean_density: f64,
+    pub final_density: f64,
+    pub entropy: f64,
+    pub transient: Option<usize>,
+    pub period: Option<usize>,
+    pub class: u8,
+}
+
+/**
+ * How many cells go into each block when measuring entropy. Single cells would
+ * just be another way of writing the density.
+ */
+const ENTROPY_BLOCK: usize = 3;
+
+fn density(state: &State) -> f64 {
+    let cells = state.cells();
+    cells.iter().filter(|c| **c != 0).count() as f64 / cells.len() as f64
+}
+
+/**
+ * Shannon entropy of the blocks of `ENTROPY_BLOCK` cells in the row, in bits
+ * per cell, so it goes from 0 for a uniform row to 1 for one that looks random
+ */
+fn entropy(state: &State) -> f64 {
+    let cells = state.cells();
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    for start in 0..cells.len() {
+        let block = (0..ENTROPY_BLOCK)
+            .map(|i| cells[(start + i) % cells.len()] != 0)
+            .fold(0, |acc, alive| (acc << 1) | usize::from(alive));
+        *counts.entry(block).or_default() += 1;
+    }
+    let total = cells.len() as f64;
+    let bits: f64 = counts
+        .values()
+        .map(|count| {
+            let p = *count as f64 / total;
+            p * (1.0 / p).log2()
+        })
+        .sum();
+    bits / ENTROPY_BLOCK as f64
+}
+
+/**
+ * A rough guess at which of Wolfram's four classes the run falls into
+ *
+ * 1. Settles into a uniform row
+ * 2. Settles into some other fixed or repeating pattern
+ * 3. Never repeats and looks random
+ * 4. Never repeats, but has more structure than that
+ *
+ * This only sees one run, so it's easily fooled by small rows (which always
+ * cycle eventually) or short runs (which don't have time to).
+ */
+fn guess_class(final_state: &State, period: Option<usize>, entropy: f64) -> u8 {
+    let cells = final_state.cells();
+    let uniform = cells.iter().all(|c| *c == cells[0]);
+    match period {
+        Some(1) if uniform => 1,
+        Some(_) => 2,
+        None if entropy > 0.8 => 3,
+        None => 4,
+    }
+}
+
+/**
+ * Runs `rule` from `initial` for up to `generations` generations, stopping
+ * early if it starts repeating itself
+ */
+pub fn measure(
+    rule: &Wolfram,
+    seed: usize,
+    initial: &State,
+    boundary: Boundary,
+    generations: usize,
+) -> Metrics {
+    let mut detector = CycleDetector::new();
+    let mut density_over_time = Vec::new();
+    let mut cycle = None;
+    let mut state = initial.clone();
+    for _ in 0..generations {
+        density_over_time.push(density(&state));
+        cycle = detector.observe(&state);
+        if cycle.is_some() {
+            break;
+        }
+        state = state.next(rule, boundary);
+    }
+
+    let entropy = entropy(&state);
+    let period = cycle.map(|c| c.period);
+    Metrics {
+        rule: rule.code(),
+        seed,
+        generations: density_over_time.len(),
+        mean_density: density_over_time.iter().sum::<f64>() / density_over_time.len().max(1) as f64,
+        final_density: density(&state),
+        density: density_over_time,
+        entropy,
+        transient: cycle.map(|c| c.transient),
+        period,
+        class: guess_class(&state, period, entropy),
+    }
+}
+
+/**
+ * Whether this is the smallest code out of the rule and the ones that only
+ * differ from it by swapping left and right or live and dead
+ */
+pub fn is_distinct(rule: &Wolfram) -> bool {
+    rule.canonical().code() == rule.code()
+}
+
+/**
+ * Leaves out the density over time, since it doesn't fit in a column
+ */
+pub fn write_csv<W: Write>(out: &mut W, metrics: &[Metrics]) -> io::Result<()> {
+    writeln!(
+        out,
+        "rule,seed,generations,mean_density,final_density,entropy,transient,period,class"
+    )?;
+    let optional = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+    for m in metrics {
+        writeln!(
+            out,
+            "{},{},{},{:.4},{:.4},{:.4},{},{},{}",
+            m.rule,
+            m.seed,
+            m.generations,
+            m.mean_density,
+            m.final_density,
+            m.entropy,
+            optional(m.transient),
+            optional(m.period),
+            m.class
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single_cell() -> State {
+        State::from_string(&format!("{0}X{0}", " ".repeat(40)))
+    }
+
+    #[test]
+    fn measures_entropy() {
+        assert_eq!(entropy(&State::from_string("      ")), 0.0);
+        assert_eq!(entropy(&State::from_string("XXXXXX")), 0.0);
+        // Every block of three shows up exactly once
+        let de_bruijn = State::from_string("   X XXX");
+        assert!((entropy(&de_bruijn) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn guesses_classes() {
+        let guess = |code| {
+            measure(
+                &Wolfram::from_code(code),
+                0,
+                &single_cell(),
+                Boundary::Periodic,
+                200,
+            )
+            .class
+        };
+        assert_eq!(guess(0), 1);
+        assert_eq!(guess(4), 2);
+        assert_eq!(guess(170), 2);
+        assert_eq!(guess(30), 3);
+    }
+
+    #[test]
+    fn records_cycles() {
+        let metrics = measure(
+            &Wolfram::from_code(0),
+            3,
+            &single_cell(),
+            Boundary::Periodic,
+            100,
+        );
+        assert_eq!(metrics.rule, 0);
+        assert_eq!(metrics.seed, 3);
+        assert_eq!(metrics.transient, Some(1));
+        assert_eq!(metrics.period, Some(1));
+        assert_eq!(metrics.generations, 3);
+        assert_eq!(metrics.final_density, 0.0);
+        assert_eq!(metrics.density, vec![1.0 / 81.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn finds_distinct_rules() {
+        let distinct = (0..=255u8)
+            .filter(|code| is_distinct(&Wolfram::from_code(*code)))
+            .count();
+        assert_eq!(distinct, 88);
+        assert!(is_distinct(&Wolfram::from_code(30)));
+        assert!(!is_distinct(&Wolfram::from_code(86)));
+        assert!(is_distinct(&Wolfram::from_code(110)));
+        assert!(!is_distinct(&Wolfram::from_code(137)));
+    }
+
+    #[test]
+    fn writes_csv() {
+        let metrics = measure(
+            &Wolfram::from_code(0),
+            0,
+            &single_cell(),
+            Boundary::Periodic,
+            100,
+        );
+        let mut out = Vec::new();
+        write_csv(&mut out, &[metrics]).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "rule,seed,generations,mean_density,final_density,entropy,transient,period,class\n\
+             0,0,3,0.0041,0.0000,0.0000,1,1,1\n"
+        );
+    }
+}
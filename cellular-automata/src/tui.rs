@@ -0,0 +1,329 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, queue, style, terminal};
+
+const SLOWEST: Duration = Duration::from_secs(2);
+const FASTEST: Duration = Duration::from_millis(10);
+
+const HELP: &str = "space pause  , . step  home end  \u{2190} \u{2192} scroll  + - speed  q quit";
+
+/**
+ * An automaton run, played back in the terminal
+ *
+ * Every generation is kept, so going back over the run doesn't need the
+ * automaton to run backwards, and going forward over it again doesn't run it
+ * twice.
+ */
+pub struct Viewer<S> {
+    history: Vec<S>,
+    /**
+     * The generation at the bottom of the screen
+     */
+    current: usize,
+    /**
+     * Whether the automaton has said there are no more generations
+     */
+    finished: bool,
+    paused: bool,
+    delay: Duration,
+    /**
+     * How many columns the rows are scrolled left by
+     */
+    scroll: usize,
+}
+
+/**
+ * What to do after a key press
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Quit,
+}
+
+impl<S> Viewer<S> {
+    pub fn new(first: S) -> Viewer<S> {
+        Viewer {
+            history: vec![first],
+            current: 0,
+            finished: false,
+            paused: false,
+            delay: Duration::from_millis(100),
+            scroll: 0,
+        }
+    }
+
+    /**
+     * Moves on a generation, working it out with `next` if it hasn't been
+     * already
+     */
+    fn forward<E>(&mut self, next: &mut impl FnMut(&S) -> Result<Option<S>, E>) -> Result<(), E> {
+        if self.current + 1 < self.history.len() {
+            self.current += 1;
+        } else if !self.finished {
+            match next(&self.history[self.current])? {
+                Some(state) => {
+                    self.history.push(state);
+                    self.current += 1;
+                }
+                None => self.finished = true,
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Whether there's nothing after the current generation, and never will be
+     */
+    fn at_end(&self) -> bool {
+        self.finished && self.current + 1 == self.history.len()
+    }
+
+    fn key<E>(
+        &mut self,
+        key: KeyCode,
+        next: &mut impl FnMut(&S) -> Result<Option<S>, E>,
+    ) -> Result<Action, E> {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(Action::Quit),
+            KeyCode::Char(' ') => self.paused = !self.paused,
+            KeyCode::Char('.') => {
+                self.paused = true;
+                self.forward(next)?;
+            }
+            KeyCode::Char(',') => {
+                self.paused = true;
+                self.current = self.current.saturating_sub(1);
+            }
+            KeyCode::Home => {
+                self.paused = true;
+                self.current = 0;
+            }
+            // Only as far as has been run, rather than running until the end,
+            // which might never come
+            KeyCode::End => self.current = self.history.len() - 1,
+            KeyCode::Left => self.scroll = self.scroll.saturating_sub(4),
+            KeyCode::Right => self.scroll += 4,
+            KeyCode::Char('+' | '=') => self.delay = (self.delay / 2).max(FASTEST),
+            KeyCode::Char('-') => self.delay = (self.delay * 2).min(SLOWEST),
+            _ => {}
+        }
+        Ok(Action::Continue)
+    }
+
+    /**
+     * The lines to draw on a `width` by `height` screen: as many generations
+     * as fit up to the current one, then a status line
+     *
+     * `render` gives the column a row starts at, which is only ever not 0 for
+     * rows that grow, and the row itself. Rows are lined up by those columns
+     * and cut down to the width of the screen.
+     */
+    fn frame(
+        &self,
+        width: usize,
+        height: usize,
+        render: &impl Fn(&S) -> (isize, String),
+    ) -> Vec<String> {
+        let rows = height.saturating_sub(1);
+        let first = (self.current + 1).saturating_sub(rows);
+        let rendered: Vec<(isize, String)> = self.history[first..=self.current]
+            .iter()
+            .map(render)
+            .collect();
+        let left = rendered
+            .iter()
+            .map(|(origin, _)| *origin)
+            .min()
+            .unwrap_or(0);
+        let mut lines: Vec<String> = rendered
+            .iter()
+            .map(|(origin, row)| {
+                let padding = " ".repeat((origin - left) as usize);
+                padding
+                    .chars()
+                    .chain(row.chars())
+                    .skip(self.scroll)
+                    .take(width)
+                    .collect()
+            })
+            .collect();
+        if height > 0 {
+            let state = match self.paused {
+                _ if self.at_end() => "finished",
+                true => "paused",
+                false => "playing",
+            };
+            let status = format!(
+                "Generation {} ({state}, {}ms)  {HELP}",
+                self.current,
+                self.delay.as_millis()
+            );
+            lines.push(status.chars().take(width).collect());
+        }
+        lines
+    }
+
+    /**
+     * Plays the run in the terminal until it's quit, taking over the whole
+     * screen while it does
+     *
+     * `next` works out the generation after the one it's given, or `None` if
+     * there aren't any more. See `frame` for `render`.
+     */
+    pub fn run<E: From<io::Error>>(
+        mut self,
+        mut next: impl FnMut(&S) -> Result<Option<S>, E>,
+        render: impl Fn(&S) -> (isize, String),
+    ) -> Result<(), E> {
+        let mut out = io::stdout();
+        let _screen = Screen::enter(&mut out)?;
+        let mut last_step = Instant::now();
+        loop {
+            let (width, height) = terminal::size()?;
+            queue!(out, terminal::Clear(terminal::ClearType::All))?;
+            for (y, line) in self
+                .frame(width.into(), height.into(), &render)
+                .iter()
+                .enumerate()
+            {
+                queue!(out, cursor::MoveTo(0, y as u16), style::Print(line))?;
+            }
+            out.flush()?;
+
+            let playing = !self.paused && !self.at_end();
+            let timeout = match playing {
+                true => self.delay.saturating_sub(last_step.elapsed()),
+                false => Duration::from_secs(60),
+            };
+            if event::poll(timeout)? {
+                match event::read()? {
+                    Event::Key(key)
+                        if key.kind == KeyEventKind::Press
+                            && self.key(key.code, &mut next)? == Action::Quit =>
+                    {
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+            } else if playing {
+                self.forward(&mut next)?;
+                last_step = Instant::now();
+            }
+        }
+    }
+}
+
+/**
+ * The alternate screen in raw mode, which is put back how it was when this is
+ * dropped, however the viewer stops
+ */
+struct Screen;
+
+impl Screen {
+    fn enter(out: &mut impl Write) -> io::Result<Screen> {
+        terminal::enable_raw_mode()?;
+        execute!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Screen)
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), terminal::LeaveAlternateScreen, cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Counts up to 5
+     */
+    fn count(n: &usize) -> Result<Option<usize>, io::Error> {
+        Ok((*n < 5).then_some(n + 1))
+    }
+
+    fn render(n: &usize) -> (isize, String) {
+        (0, n.to_string().repeat(*n + 1))
+    }
+
+    #[test]
+    fn steps_back_and_forth() {
+        let mut calls = 0;
+        let mut next = |n: &usize| {
+            calls += 1;
+            count(n)
+        };
+        let mut viewer = Viewer::new(0);
+        for _ in 0..3 {
+            viewer.key(KeyCode::Char('.'), &mut next).unwrap();
+        }
+        assert!(viewer.paused);
+        assert_eq!(viewer.current, 3);
+        viewer.key(KeyCode::Char(','), &mut next).unwrap();
+        viewer.key(KeyCode::Char(','), &mut next).unwrap();
+        assert_eq!(viewer.current, 1);
+        viewer.key(KeyCode::Char('.'), &mut next).unwrap();
+        viewer.key(KeyCode::End, &mut next).unwrap();
+        assert_eq!(viewer.current, 3);
+        viewer.key(KeyCode::Home, &mut next).unwrap();
+        assert_eq!(viewer.current, 0);
+        viewer.key(KeyCode::Char(','), &mut next).unwrap();
+        assert_eq!(viewer.current, 0);
+
+        // Running off the end
+        for _ in 0..10 {
+            viewer.key(KeyCode::Char('.'), &mut next).unwrap();
+        }
+        assert_eq!(viewer.current, 5);
+        assert!(viewer.finished);
+        assert_eq!(
+            viewer.key(KeyCode::Char('q'), &mut next).unwrap(),
+            Action::Quit
+        );
+        // Each generation was only worked out once, plus finding the end
+        assert_eq!(calls, 6);
+    }
+
+    #[test]
+    fn changes_speed_within_limits() {
+        let mut viewer = Viewer::new(0);
+        for _ in 0..20 {
+            viewer.key(KeyCode::Char('+'), &mut count).unwrap();
+        }
+        assert_eq!(viewer.delay, FASTEST);
+        for _ in 0..20 {
+            viewer.key(KeyCode::Char('-'), &mut count).unwrap();
+        }
+        assert_eq!(viewer.delay, SLOWEST);
+    }
+
+    #[test]
+    fn frames_fit_the_screen() {
+        let mut viewer = Viewer::new(0);
+        for _ in 0..4 {
+            viewer.forward(&mut count).unwrap();
+        }
+        let frame = viewer.frame(3, 3, &render);
+        assert_eq!(frame[..2], ["333", "444"]);
+        assert!(frame[2].starts_with("Gen"));
+
+        viewer.key(KeyCode::Right, &mut count).unwrap();
+        assert_eq!(viewer.frame(80, 3, &render)[..2], ["", "4"]);
+        assert!(viewer.frame(80, 0, &render).is_empty());
+    }
+
+    #[test]
+    fn lines_up_growing_rows() {
+        let grow = |n: &usize| (-(*n as isize), "X".repeat(2 * n + 1));
+        let mut viewer = Viewer::new(0);
+        viewer.history = vec![0, 1, 2];
+        viewer.current = 2;
+        assert_eq!(viewer.frame(80, 4, &grow)[..3], ["  X", " XXX", "XXXXX"]);
+    }
+}